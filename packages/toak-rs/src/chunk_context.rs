@@ -0,0 +1,69 @@
+//! Context headers prepended to chunks when they are embedded, so a chunk taken from the middle
+//! of a file still carries where it came from: its path, language and enclosing item or section.
+use crate::data_summarizer::DataFormat;
+use crate::language_cleaner::CodeLanguage;
+use crate::prose_chunker::ProseFormat;
+use std::path::Path;
+
+/// Human-readable name of the language of `file_path`, from the formats the cleaners, chunkers
+/// and summarizers already recognize.
+pub fn language_name(file_path: &str) -> Option<&'static str> {
+  CodeLanguage::from_path(file_path)
+    .map(CodeLanguage::name)
+    .or_else(|| ProseFormat::from_path(file_path).map(ProseFormat::name))
+    .or_else(|| DataFormat::from_path(Path::new(file_path)).map(DataFormat::name))
+}
+
+/// Builds the context header of a chunk of `file_path`, one `Key: value` line per known field,
+/// e.g. `File: src/widget.rs`, `Language: Rust` and `Item: impl Widget > fn draw(&self)`.
+/// Documents name their enclosing `Section` instead of an item.
+pub fn context_header(file_path: &str, parent: Option<&str>, heading_path: Option<&str>) -> String {
+  let mut header = format!("File: {}", file_path);
+  if let Some(language) = language_name(file_path) {
+    header.push_str(&format!("\nLanguage: {}", language));
+  }
+  if let Some(parent) = parent {
+    header.push_str(&format!("\nItem: {}", parent));
+  }
+  if let Some(heading_path) = heading_path {
+    header.push_str(&format!("\nSection: {}", heading_path));
+  }
+  header
+}
+
+/// The text embedded for a chunk: its context header, if any, followed by its content.
+pub fn embedding_input(context: Option<&str>, content: &str) -> String {
+  match context {
+    Some(context) => format!("{}\n\n{}", context, content),
+    None => content.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_context_header() {
+    let header = context_header("src/widget.rs", Some("impl Widget > fn draw(&self)"), None);
+    assert_eq!(
+      header,
+      "File: src/widget.rs\nLanguage: Rust\nItem: impl Widget > fn draw(&self)"
+    );
+
+    let header = context_header("docs/install.md", None, Some("Install > Linux"));
+    assert_eq!(
+      header,
+      "File: docs/install.md\nLanguage: Markdown\nSection: Install > Linux"
+    );
+
+    assert_eq!(context_header("Cargo.lock", None, None), "File: Cargo.lock");
+    assert_eq!(language_name("web/app.tsx"), Some("JavaScript/TypeScript"));
+    assert_eq!(language_name("data/users.csv"), Some("CSV"));
+    assert_eq!(
+      embedding_input(Some("File: a.rs"), "fn a() {}"),
+      "File: a.rs\n\nfn a() {}"
+    );
+    assert_eq!(embedding_input(None, "fn a() {}"), "fn a() {}");
+  }
+}
@@ -1,6 +1,11 @@
 //! Command line arguments backing the `toak` binary.
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use toak_rs::{
+  find_model, ChunkingStrategy, CodeLanguage, CommentMode, EmbeddingBackendConfig, EmbeddingModel,
+  InjectionPolicy, ModelManifest, OpenAiConfig, PiiKind, RedactedLinePolicy, ScanFormat, Severity,
+  UnicodePolicy, HASHING_MODEL_PREFIX, MAX_HASHING_DIMENSION,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -34,6 +39,103 @@ pub enum Commands {
     /// Preset prompt template to use
     #[arg(long, short = 'p')]
     prompt: Option<String>,
+
+    /// Write a JSON report of every redacted secret (location, rule, fingerprint) to this path
+    #[arg(long)]
+    redaction_report: Option<PathBuf>,
+
+    /// Replace each distinct secret with a stable [SECRET_n] placeholder instead of [REDACTED]
+    #[arg(long)]
+    pseudonymize_secrets: bool,
+
+    /// What to do with lines containing a redacted secret: drop, redact (value only) or comment
+    #[arg(long, default_value = "redact")]
+    redacted_lines: RedactedLinePolicy,
+
+    /// Also redact personal data (emails, IP addresses, phone numbers) with typed placeholders
+    #[arg(long)]
+    redact_pii: bool,
+
+    /// Restrict PII redaction to these types (email, ipv4, ipv6, phone); implies --redact-pii
+    #[arg(long, value_delimiter = ',')]
+    pii_types: Vec<PiiKind>,
+
+    /// Email domain whose addresses are kept (repeatable); example.com/.org/.net are always kept
+    #[arg(long)]
+    pii_allow_domain: Vec<String>,
+
+    /// Bidi controls, invisible characters and homoglyph identifiers: strip, escape or report
+    #[arg(long, default_value = "strip")]
+    unicode: UnicodePolicy,
+
+    /// Flag likely prompt-injection passages and annotate, fence or drop them
+    #[arg(long, value_name = "POLICY")]
+    prompt_injection: Option<InjectionPolicy>,
+
+    /// Keep imports and debug logging for these languages (rust, python, go, java, kotlin, c, javascript)
+    #[arg(long, value_delimiter = ',', value_name = "LANGUAGES")]
+    keep_imports_for: Vec<CodeLanguage>,
+
+    /// Documentation comments in prompt.md: keep (rustdoc, docstrings, JSDoc) or strip
+    #[arg(long, default_value = "strip")]
+    doc_comments: CommentMode,
+
+    /// Documentation comments in embedded chunks: keep or strip
+    #[arg(long, default_value = "keep")]
+    embedding_doc_comments: CommentMode,
+
+    /// Keep license headers and banners repeated across files instead of removing them
+    #[arg(long)]
+    keep_license_headers: bool,
+
+    /// Data files (JSON, CSV, YAML, SQL) at least this large are replaced by a structural summary
+    #[arg(long, default_value_t = 65536, value_name = "BYTES")]
+    data_summary_threshold: usize,
+
+    /// Sample records, rows or sequence items kept in data file summaries
+    #[arg(long, default_value_t = 5, value_name = "N")]
+    data_summary_records: usize,
+
+    /// Include data files whole instead of summarizing large ones
+    #[arg(long)]
+    no_data_summary: bool,
+
+    /// Treat lockfiles (Cargo.lock, package-lock.json, bun.lock, poetry.lock, go.sum) as regular files instead of summarizing their dependencies
+    #[arg(long)]
+    no_dependency_summary: bool,
+
+    /// How embedded chunks are split: syntax (whole functions and classes, document sections) or lines
+    #[arg(long, default_value = "syntax", value_name = "STRATEGY")]
+    chunking: ChunkingStrategy,
+
+    /// Embed chunks without the header naming their file, language and enclosing item or section
+    #[arg(long)]
+    no_context_headers: bool,
+
+    #[command(flatten)]
+    embedding: EmbeddingArgs,
+  },
+  /// Scan the selected files for secrets without generating anything
+  Scan {
+    /// Project directory to scan
+    #[arg(long, short = 'd')]
+    dir: Option<PathBuf>,
+
+    /// Output format: human, json or sarif
+    #[arg(long, default_value = "human")]
+    format: ScanFormat,
+
+    /// Exit with status 1 when any finding is at or above this severity (low, medium, high)
+    #[arg(long, default_value = "low")]
+    severity_threshold: Severity,
+
+    /// Write the report to this file instead of stdout
+    #[arg(long, short = 'o')]
+    output: Option<PathBuf>,
+
+    /// Record all current findings in .toak/secrets-baseline.json so they are ignored from now on
+    #[arg(long)]
+    update_baseline: bool,
   },
   /// Search the embeddings database using semantic similarity
   Search {
@@ -51,6 +153,106 @@ pub enum Commands {
     /// Show full content of results (not just preview)
     #[arg(long)]
     full: bool,
+
+    #[command(flatten)]
+    embedding: EmbeddingArgs,
+  },
+  /// Manage local embedding models
+  Model {
+    #[command(subcommand)]
+    command: ModelCommand,
   },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ModelCommand {
+  /// List the local embedding models that can be selected with --model
+  List,
+  /// Download a model into the local model cache
+  Fetch {
+    /// Model to download, by catalog code or name
+    #[arg(long, value_name = "MODEL", value_parser = parse_model)]
+    model: Option<EmbeddingModel>,
+  },
+  /// Package a model as a directory that --model-dir loads without network access
+  Export {
+    /// Model to export, by catalog code or name; it is downloaded first if needed
+    #[arg(long, value_name = "MODEL", value_parser = parse_model)]
+    model: Option<EmbeddingModel>,
+
+    /// Directory to write the model to
+    #[arg(long, short = 'o', value_name = "DIR")]
+    output: PathBuf,
+  },
+}
+
+/// Where embeddings come from, shared by `generate` and `search` so queries are embedded by the
+/// same model as the database.
+#[derive(clap::Args, Debug)]
+pub struct EmbeddingArgs {
+  /// Local embedding model, by catalog code (e.g. Xenova/bge-small-en-v1.5) or name, or toak/hashing-<DIMENSION> for the model-free hashing embedder; see `toak model list`. Searches default to the model the database was generated with
+  #[arg(long, env = "TOAK_MODEL", value_name = "MODEL", value_parser = parse_backend)]
+  pub model: Option<EmbeddingBackendConfig>,
+
+  /// Load the local model from a directory written by `toak model export` instead of downloading it
+  #[arg(long, env = "TOAK_MODEL_DIR", value_name = "DIR", value_parser = parse_model_dir, conflicts_with = "model")]
+  pub model_dir: Option<PathBuf>,
+
+  /// Embed with an OpenAI-compatible API at this base URL (e.g. http://localhost:8080/v1) instead of the local model; the API key is read from TOAK_EMBEDDING_API_KEY or OPENAI_API_KEY
+  #[arg(long, value_name = "URL", requires = "embedding_api_model")]
+  pub embedding_url: Option<String>,
+
+  /// Model name sent to the embeddings API
+  #[arg(long, value_name = "NAME", requires = "embedding_url")]
+  pub embedding_api_model: Option<String>,
+
+  /// Requests sent to the embeddings API at once
+  #[arg(long, default_value_t = 4, value_name = "N")]
+  pub embedding_concurrency: usize,
+
+  /// Maximum input length of the API model in tokens
+  #[arg(long, default_value_t = 8192, value_name = "TOKENS")]
+  pub embedding_max_tokens: usize,
+
+  /// tokenizer.json of the API model, to count tokens exactly instead of estimating them
+  #[arg(long, value_name = "FILE")]
+  pub embedding_tokenizer: Option<PathBuf>,
+}
+
+fn parse_model(name: &str) -> Result<EmbeddingModel, String> {
+  find_model(name).ok_or_else(|| format!("unknown embedding model {}; run `toak model list` to see the available ones", name))
+}
+
+fn parse_backend(name: &str) -> Result<EmbeddingBackendConfig, String> {
+  EmbeddingBackendConfig::for_model(name).ok_or_else(|| match name.strip_prefix(HASHING_MODEL_PREFIX) {
+    Some(_) => format!("the hashing embedder's dimension must be between 1 and {}", MAX_HASHING_DIMENSION),
+    None => format!("unknown embedding model {}; run `toak model list` to see the available ones", name),
+  })
+}
+
+fn parse_model_dir(dir: &str) -> Result<PathBuf, String> {
+  let dir = PathBuf::from(dir);
+  ModelManifest::load(&dir).map_err(|e| format!("{:#}", e))?;
+  Ok(dir)
+}
+
+impl EmbeddingArgs {
+  /// The selected backend: the embeddings API if given, else the model directory or the
+  /// selected local model, or `None` when none was chosen.
+  pub fn backend(&self) -> Option<EmbeddingBackendConfig> {
+    let (Some(url), Some(model)) = (&self.embedding_url, &self.embedding_api_model) else {
+      return match &self.model_dir {
+        Some(dir) => Some(EmbeddingBackendConfig::ModelDir(dir.clone())),
+        None => self.model.clone(),
+      };
+    };
+    Some(EmbeddingBackendConfig::OpenAi(OpenAiConfig {
+      api_key: std::env::var("TOAK_EMBEDDING_API_KEY").or_else(|_| std::env::var("OPENAI_API_KEY")).ok(),
+      max_concurrency: self.embedding_concurrency,
+      max_input_length: self.embedding_max_tokens,
+      tokenizer_file: self.embedding_tokenizer.clone(),
+      ..OpenAiConfig::new(url.as_str(), model.as_str())
+    }))
+  }
+}
+
@@ -0,0 +1,669 @@
+//! Compact descriptions of large structured data files. Above a size threshold, JSON, JSON
+//! Lines, CSV/TSV, YAML and SQL dumps are replaced by their inferred structure plus the first few
+//! records, and SQL dumps keep their DDL while inserts are dropped.
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Number of records inspected when inferring a schema
+const SCHEMA_SAMPLE: usize = 100;
+/// Longest string kept in sample records
+const MAX_SAMPLE_STRING: usize = 200;
+
+/// Structured data formats the summarizer understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+  Json,
+  JsonLines,
+  Csv,
+  Tsv,
+  Yaml,
+  Sql,
+}
+
+impl DataFormat {
+  /// Format of `path` judged by its extension.
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+      "json" | "geojson" => Some(Self::Json),
+      "jsonl" | "ndjson" => Some(Self::JsonLines),
+      "csv" => Some(Self::Csv),
+      "tsv" => Some(Self::Tsv),
+      "yaml" | "yml" => Some(Self::Yaml),
+      "sql" => Some(Self::Sql),
+      _ => None,
+    }
+  }
+
+  /// Human-readable name of the format.
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Json => "JSON",
+      Self::JsonLines => "JSON Lines",
+      Self::Csv => "CSV",
+      Self::Tsv => "TSV",
+      Self::Yaml => "YAML",
+      Self::Sql => "SQL",
+    }
+  }
+}
+
+/// When and how data files are summarized.
+#[derive(Debug, Clone)]
+pub struct DataSummaryOptions {
+  /// Files at least this many bytes long are summarized; smaller ones are kept whole
+  pub threshold_bytes: usize,
+  /// Records, rows or sequence items shown as a sample
+  pub sample_records: usize,
+}
+
+impl Default for DataSummaryOptions {
+  fn default() -> Self {
+    Self {
+      threshold_bytes: 64 * 1024,
+      sample_records: 5,
+    }
+  }
+}
+
+/// Summarizes `content` if `path` is a supported data file at or above the size threshold.
+/// Returns `None` when the content should be used as is, including when it fails to parse.
+pub fn summarize_data_file(
+  path: &Path,
+  content: &str,
+  options: &DataSummaryOptions,
+) -> Option<String> {
+  let format = DataFormat::from_path(path)?;
+  if content.len() < options.threshold_bytes {
+    return None;
+  }
+
+  let body = match format {
+    DataFormat::Json => summarize_json(content, options)?,
+    DataFormat::JsonLines => summarize_json_lines(content, options)?,
+    DataFormat::Csv => summarize_delimited(content, ',', options)?,
+    DataFormat::Tsv => summarize_delimited(content, '\t', options)?,
+    DataFormat::Yaml => summarize_yaml(content, options),
+    DataFormat::Sql => summarize_sql(content),
+  };
+  Some(format!(
+    "[toak: summary of a {} KiB {} file]\n{}",
+    content.len().div_ceil(1024),
+    format.name(),
+    body
+  ))
+}
+
+/// Inferred structure of JSON values.
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+  Null,
+  Bool,
+  Number,
+  String,
+  Array(Box<Shape>, usize),
+  Object(BTreeMap<String, Shape>),
+  Mixed(Vec<Shape>),
+  /// Element shape of an empty array
+  Unknown,
+}
+
+impl Shape {
+  fn of(value: &Value) -> Self {
+    match value {
+      Value::Null => Self::Null,
+      Value::Bool(_) => Self::Bool,
+      Value::Number(_) => Self::Number,
+      Value::String(_) => Self::String,
+      Value::Array(items) => Self::Array(
+        Box::new(Self::of_all(items.iter().take(SCHEMA_SAMPLE))),
+        items.len(),
+      ),
+      Value::Object(map) => {
+        Self::Object(map.iter().map(|(k, v)| (k.clone(), Self::of(v))).collect())
+      }
+    }
+  }
+
+  fn of_all<'a>(values: impl Iterator<Item = &'a Value>) -> Self {
+    values
+      .map(Self::of)
+      .reduce(Self::merge)
+      .unwrap_or(Self::Unknown)
+  }
+
+  fn merge(self, other: Self) -> Self {
+    match (self, other) {
+      (a, b) if a == b => a,
+      (Self::Unknown, b) => b,
+      (a, Self::Unknown) => a,
+      (Self::Object(mut a), Self::Object(b)) => {
+        for (key, shape) in b {
+          let merged = match a.remove(&key) {
+            Some(existing) => existing.merge(shape),
+            None => shape,
+          };
+          a.insert(key, merged);
+        }
+        Self::Object(a)
+      }
+      (Self::Array(a, n), Self::Array(b, m)) => Self::Array(Box::new(a.merge(*b)), n.max(m)),
+      (Self::Mixed(mut shapes), other) | (other, Self::Mixed(mut shapes)) => {
+        if !shapes.contains(&other) {
+          shapes.push(other);
+        }
+        Self::Mixed(shapes)
+      }
+      (a, b) => Self::Mixed(vec![a, b]),
+    }
+  }
+
+  fn render(&self, indent: usize, out: &mut String) {
+    match self {
+      Self::Null => out.push_str("null"),
+      Self::Bool => out.push_str("boolean"),
+      Self::Number => out.push_str("number"),
+      Self::String => out.push_str("string"),
+      Self::Unknown => out.push_str("unknown"),
+      Self::Array(element, len) => {
+        out.push_str(&format!("array[{}] of ", len));
+        element.render(indent, out);
+      }
+      Self::Mixed(shapes) => {
+        for (i, shape) in shapes.iter().enumerate() {
+          if i > 0 {
+            out.push_str(" | ");
+          }
+          shape.render(indent, out);
+        }
+      }
+      Self::Object(fields) => {
+        out.push_str("{\n");
+        for (key, shape) in fields {
+          out.push_str(&format!("{}{}: ", "  ".repeat(indent + 1), key));
+          shape.render(indent + 1, out);
+          out.push('\n');
+        }
+        out.push_str(&format!("{}}}", "  ".repeat(indent)));
+      }
+    }
+  }
+}
+
+/// Copy of `value` with arrays cut to `max_items` and long strings shortened.
+fn truncate_value(value: &Value, max_items: usize) -> Value {
+  match value {
+    Value::Array(items) => Value::Array(
+      items
+        .iter()
+        .take(max_items)
+        .map(|v| truncate_value(v, max_items))
+        .collect(),
+    ),
+    Value::Object(map) => Value::Object(
+      map
+        .iter()
+        .map(|(k, v)| (k.clone(), truncate_value(v, max_items)))
+        .collect(),
+    ),
+    Value::String(s) if s.chars().count() > MAX_SAMPLE_STRING => Value::String(format!(
+      "{}...",
+      s.chars().take(MAX_SAMPLE_STRING).collect::<String>()
+    )),
+    other => other.clone(),
+  }
+}
+
+fn summarize_json(content: &str, options: &DataSummaryOptions) -> Option<String> {
+  let value: Value = serde_json::from_str(content).ok()?;
+  let mut schema = String::new();
+  Shape::of(&value).render(0, &mut schema);
+  let sample =
+    serde_json::to_string_pretty(&truncate_value(&value, options.sample_records)).ok()?;
+  Some(format!(
+    "Schema:\n{}\n\nSample (arrays cut to {} items):\n{}",
+    schema, options.sample_records, sample
+  ))
+}
+
+fn summarize_json_lines(content: &str, options: &DataSummaryOptions) -> Option<String> {
+  let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+  let values: Vec<Value> = lines
+    .iter()
+    .take(SCHEMA_SAMPLE)
+    .map(|l| serde_json::from_str(l).ok())
+    .collect::<Option<_>>()?;
+  let mut schema = String::new();
+  Shape::of_all(values.iter()).render(0, &mut schema);
+  let sample: Vec<String> = values
+    .iter()
+    .take(options.sample_records)
+    .filter_map(|v| serde_json::to_string(&truncate_value(v, options.sample_records)).ok())
+    .collect();
+  Some(format!(
+    "{} records\n\nRecord schema:\n{}\n\nFirst {} records:\n{}",
+    lines.len(),
+    schema,
+    sample.len(),
+    sample.join("\n")
+  ))
+}
+
+/// One record of a delimited file: its text as written and its fields.
+struct Record<'a> {
+  raw: &'a str,
+  fields: Vec<String>,
+}
+
+/// Splits delimited content into records, honoring double-quoted fields, which may hold
+/// delimiters and line breaks. Blank lines are skipped.
+fn delimited_records(content: &str, delimiter: char) -> Vec<Record<'_>> {
+  let mut records = Vec::new();
+  let mut fields = Vec::new();
+  let mut field = String::new();
+  let mut quoted = false;
+  let mut start = 0;
+  let mut chars = content.char_indices().peekable();
+
+  let mut finish = |start: usize, end: usize, fields: &mut Vec<String>, field: &mut String| {
+    fields.push(std::mem::take(field));
+    let raw = content[start..end].trim_end_matches('\r');
+    let fields = std::mem::take(fields);
+    if !raw.trim().is_empty() {
+      records.push(Record { raw, fields });
+    }
+  };
+
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '"' if quoted && chars.peek().map(|&(_, next)| next) == Some('"') => {
+        field.push('"');
+        chars.next();
+      }
+      '"' => quoted = !quoted,
+      '\n' if !quoted => {
+        finish(start, i, &mut fields, &mut field);
+        start = i + 1;
+      }
+      c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+      c => field.push(c),
+    }
+  }
+  if start < content.len() {
+    finish(start, content.len(), &mut fields, &mut field);
+  }
+  records
+}
+
+fn column_type(values: &[&str]) -> &'static str {
+  let present: Vec<&str> = values
+    .iter()
+    .map(|v| v.trim())
+    .filter(|v| !v.is_empty())
+    .collect();
+  if present.is_empty() {
+    "empty"
+  } else if present.iter().all(|v| v.parse::<i64>().is_ok()) {
+    "integer"
+  } else if present.iter().all(|v| v.parse::<f64>().is_ok()) {
+    "number"
+  } else if present
+    .iter()
+    .all(|v| matches!(v.to_ascii_lowercase().as_str(), "true" | "false"))
+  {
+    "boolean"
+  } else {
+    "string"
+  }
+}
+
+fn summarize_delimited(
+  content: &str,
+  delimiter: char,
+  options: &DataSummaryOptions,
+) -> Option<String> {
+  let mut records = delimited_records(content, delimiter).into_iter();
+  let header = records.next()?;
+  let rows: Vec<Record> = records.collect();
+
+  let columns: Vec<String> = header
+    .fields
+    .iter()
+    .enumerate()
+    .map(|(i, name)| {
+      let values: Vec<&str> = rows
+        .iter()
+        .take(SCHEMA_SAMPLE)
+        .filter_map(|r| r.fields.get(i).map(String::as_str))
+        .collect();
+      format!("  {}: {}", name.trim(), column_type(&values))
+    })
+    .collect();
+
+  let mut sample = vec![header.raw];
+  sample.extend(rows.iter().take(options.sample_records).map(|r| r.raw));
+  Some(format!(
+    "{} rows, {} columns\n\nColumns:\n{}\n\nFirst {} rows:\n{}",
+    rows.len(),
+    header.fields.len(),
+    columns.join("\n"),
+    rows.len().min(options.sample_records),
+    sample.join("\n")
+  ))
+}
+
+/// A YAML sequence being read, by indentation of its `-` markers.
+struct Sequence {
+  indent: usize,
+  items: usize,
+}
+
+/// Keeps the YAML as written, but cuts every sequence to the first few items.
+fn summarize_yaml(content: &str, options: &DataSummaryOptions) -> String {
+  let mut out: Vec<String> = Vec::new();
+  let mut stack: Vec<Sequence> = Vec::new();
+
+  let close = |seq: Sequence, stack: &[Sequence], out: &mut Vec<String>| {
+    let hidden = stack.iter().any(|s| s.items > options.sample_records);
+    if seq.items > options.sample_records && !hidden {
+      out.push(format!(
+        "{}# ... {} more items",
+        " ".repeat(seq.indent),
+        seq.items - options.sample_records
+      ));
+    }
+  };
+
+  for line in content.lines() {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() {
+      if !stack.iter().any(|s| s.items > options.sample_records) {
+        out.push(String::new());
+      }
+      continue;
+    }
+    let indent = line.len() - trimmed.len();
+    let is_item = trimmed == "-" || trimmed.starts_with("- ");
+
+    while let Some(top) = stack.last() {
+      if indent < top.indent || (indent == top.indent && !is_item) {
+        let seq = stack.pop().expect("non-empty stack");
+        close(seq, &stack, &mut out);
+      } else {
+        break;
+      }
+    }
+
+    if is_item {
+      match stack.last_mut() {
+        Some(top) if top.indent == indent => top.items += 1,
+        _ => stack.push(Sequence { indent, items: 1 }),
+      }
+    }
+
+    if !stack.iter().any(|s| s.items > options.sample_records) {
+      out.push(line.to_string());
+    }
+  }
+  while let Some(seq) = stack.pop() {
+    close(seq, &stack, &mut out);
+  }
+
+  format!(
+    "Sequences cut to {} items:\n{}",
+    options.sample_records,
+    out.join("\n")
+  )
+}
+
+static INSERT_TABLE: OnceLock<Regex> = OnceLock::new();
+
+/// The dollar-quote tag (`$$` or `$name$`) starting `rest`, if any.
+fn dollar_quote_tag(rest: &str) -> Option<&str> {
+  let body = rest.strip_prefix('$')?;
+  let end = body.find('$')?;
+  let name = &body[..end];
+  let valid = name
+    .chars()
+    .next()
+    .is_none_or(|c| c.is_alphabetic() || c == '_')
+    && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+  valid.then(|| &rest[..end + 2])
+}
+
+/// Splits SQL into statements at semicolons outside quotes, dollar-quoted bodies and comments,
+/// dropping `--` and `/* */` comments.
+fn sql_statements(content: &str) -> Vec<String> {
+  let mut statements = Vec::new();
+  let mut current = String::new();
+  let mut quote: Option<char> = None;
+  let mut chars = content.char_indices().peekable();
+
+  while let Some((i, c)) = chars.next() {
+    match quote {
+      Some(q) => {
+        current.push(c);
+        if c == q {
+          quote = None;
+        }
+      }
+      None => match c {
+        '\'' | '"' | '`' => {
+          quote = Some(c);
+          current.push(c);
+        }
+        '$' if dollar_quote_tag(&content[i..]).is_some() => {
+          // Function bodies hold whole statements: copy through the closing tag
+          let tag = dollar_quote_tag(&content[i..]).expect("checked tag");
+          let body_start = i + tag.len();
+          let end = content[body_start..]
+            .find(tag)
+            .map_or(content.len(), |p| body_start + p + tag.len());
+          current.push_str(&content[i..end]);
+          while chars.peek().is_some_and(|&(j, _)| j < end) {
+            chars.next();
+          }
+        }
+        '-' if chars.peek().map(|&(_, next)| next) == Some('-') => {
+          for (_, c) in chars.by_ref() {
+            if c == '\n' {
+              current.push('\n');
+              break;
+            }
+          }
+        }
+        '/' if chars.peek().map(|&(_, next)| next) == Some('*') => {
+          // Block comments nest in PostgreSQL
+          chars.next();
+          let mut depth = 1;
+          while depth > 0 {
+            match chars.next() {
+              Some((_, '*')) if chars.peek().map(|&(_, next)| next) == Some('/') => {
+                chars.next();
+                depth -= 1;
+              }
+              Some((_, '/')) if chars.peek().map(|&(_, next)| next) == Some('*') => {
+                chars.next();
+                depth += 1;
+              }
+              Some(_) => {}
+              None => break,
+            }
+          }
+          current.push(' ');
+        }
+        ';' => {
+          statements.push(std::mem::take(&mut current).trim().to_string());
+        }
+        c => current.push(c),
+      },
+    }
+  }
+  if !current.trim().is_empty() {
+    statements.push(current.trim().to_string());
+  }
+  statements
+}
+
+/// Keeps schema statements of a SQL dump and counts the dropped data statements per table.
+fn summarize_sql(content: &str) -> String {
+  let insert_table = INSERT_TABLE.get_or_init(|| {
+    Regex::new(r#"(?i)^(?:INSERT\s+INTO|COPY)\s+([`"\w.]+)"#).expect("valid insert pattern")
+  });
+
+  // COPY ... FROM stdin blocks carry their rows until a `\.` line
+  let mut without_copy_data = String::with_capacity(content.len());
+  let mut in_copy = false;
+  for line in content.lines() {
+    if in_copy {
+      in_copy = line.trim() != "\\.";
+      continue;
+    }
+    if line.trim_start().to_ascii_uppercase().starts_with("COPY ")
+      && line.to_ascii_lowercase().contains("from stdin")
+    {
+      in_copy = true;
+    }
+    without_copy_data.push_str(line);
+    without_copy_data.push('\n');
+  }
+
+  let mut ddl = Vec::new();
+  let mut dropped: BTreeMap<String, usize> = BTreeMap::new();
+  for statement in sql_statements(&without_copy_data) {
+    let keyword = statement
+      .split_whitespace()
+      .next()
+      .unwrap_or_default()
+      .to_ascii_uppercase();
+    match keyword.as_str() {
+      "CREATE" | "ALTER" | "COMMENT" => ddl.push(format!("{};", statement)),
+      _ => {
+        if let Some(caps) = insert_table.captures(&statement) {
+          *dropped
+            .entry(caps[1].trim_matches(|c| c == '`' || c == '"').to_string())
+            .or_default() += 1;
+        }
+      }
+    }
+  }
+
+  let mut summary = format!("Schema ({} statements):\n{}", ddl.len(), ddl.join("\n\n"));
+  if !dropped.is_empty() {
+    let counts: Vec<String> = dropped
+      .iter()
+      .map(|(table, n)| format!("{}: {}", table, n))
+      .collect();
+    summary.push_str(&format!(
+      "\n\nData statements omitted: {}",
+      counts.join(", ")
+    ));
+  }
+  summary
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn small_threshold() -> DataSummaryOptions {
+    DataSummaryOptions {
+      threshold_bytes: 0,
+      sample_records: 2,
+    }
+  }
+
+  #[test]
+  fn test_threshold_and_unsupported_files() {
+    let options = DataSummaryOptions::default();
+    assert!(summarize_data_file(Path::new("a.json"), "[1, 2]", &options).is_none());
+    assert!(summarize_data_file(Path::new("a.rs"), "fn main() {}", &small_threshold()).is_none());
+    assert!(summarize_data_file(Path::new("bad.json"), "{oops", &small_threshold()).is_none());
+  }
+
+  #[test]
+  fn test_json_schema_and_sample() {
+    let json =
+      r#"{"users": [{"id": 1, "name": "a"}, {"id": 2, "email": null}, {"id": 3}], "total": 3}"#;
+    let summary =
+      summarize_data_file(Path::new("data/users.json"), json, &small_threshold()).unwrap();
+    assert!(summary.starts_with("[toak: summary of a 1 KiB JSON file]\nSchema:\n{\n  total: number\n  users: array[3] of {\n    email: null\n    id: number\n    name: string\n  }\n}"), "{}", summary);
+    assert!(summary.contains("\"id\": 2"));
+    assert!(!summary.contains("\"id\": 3"));
+  }
+
+  #[test]
+  fn test_csv_and_json_lines() {
+    let csv = "id,name,score\n1,\"Smith, J\",9.5\n2,Lee,7\n3,Kim,\n";
+    let summary = summarize_data_file(Path::new("scores.csv"), csv, &small_threshold()).unwrap();
+    assert!(summary
+      .contains("3 rows, 3 columns\n\nColumns:\n  id: integer\n  name: string\n  score: number"));
+    assert!(summary.contains("First 2 rows:\nid,name,score\n1,\"Smith, J\",9.5\n2,Lee,7"));
+    assert!(!summary.contains("Kim"));
+
+    // Quoted fields may span lines
+    let csv = "id,note\n1,\"first line\nsecond, line\"\n2,plain\n3,last\n";
+    let summary = summarize_data_file(Path::new("notes.csv"), csv, &small_threshold()).unwrap();
+    assert!(summary.contains("3 rows, 2 columns"));
+    assert!(summary.contains("First 2 rows:\nid,note\n1,\"first line\nsecond, line\"\n2,plain"));
+
+    let jsonl = "{\"a\": 1}\n{\"a\": \"x\", \"b\": true}\n{\"a\": 3}\n";
+    let summary =
+      summarize_data_file(Path::new("events.ndjson"), jsonl, &small_threshold()).unwrap();
+    assert!(
+      summary.contains("3 records\n\nRecord schema:\n{\n  a: number | string\n  b: boolean\n}")
+    );
+  }
+
+  #[test]
+  fn test_yaml_sequences_are_cut() {
+    let yaml = "name: app\nitems:\n  - id: 1\n    tags:\n      - a\n      - b\n      - c\n  - id: 2\n  - id: 3\n  - id: 4\nfooter: true";
+    let summary = summarize_yaml(yaml, &small_threshold());
+    assert_eq!(
+      summary,
+      "Sequences cut to 2 items:\nname: app\nitems:\n  - id: 1\n    tags:\n      - a\n      - b\n      # ... 1 more items\n  - id: 2\n  # ... 2 more items\nfooter: true"
+    );
+  }
+
+  #[test]
+  fn test_sql_dump_keeps_ddl() {
+    let sql = "\
+-- dump header
+CREATE TABLE users (id int, name text);
+INSERT INTO users VALUES (1, 'a;b');
+INSERT INTO `users` VALUES (2, 'c');
+ALTER TABLE users ADD PRIMARY KEY (id);
+COPY public.orders (id) FROM stdin;
+1
+2
+\\.
+SET search_path = public;
+";
+    let summary = summarize_data_file(Path::new("dump.sql"), sql, &small_threshold()).unwrap();
+    assert_eq!(
+      summary,
+      "[toak: summary of a 1 KiB SQL file]\nSchema (2 statements):\nCREATE TABLE users (id int, name text);\n\nALTER TABLE users ADD PRIMARY KEY (id);\n\nData statements omitted: public.orders: 1, users: 2"
+    );
+  }
+
+  #[test]
+  fn test_sql_dollar_quotes_and_block_comments() {
+    let sql = "\
+/* dump; generated */
+CREATE FUNCTION touch() RETURNS trigger AS $body$
+BEGIN
+  NEW.updated = now(); RETURN NEW;
+END;
+$body$ LANGUAGE plpgsql;
+INSERT INTO logs VALUES ($$a;b$$);
+";
+    let statements = sql_statements(sql);
+    assert_eq!(statements.len(), 2);
+    assert!(statements[0].starts_with("CREATE FUNCTION touch()"));
+    assert!(statements[0].ends_with("$body$ LANGUAGE plpgsql"));
+    assert_eq!(statements[1], "INSERT INTO logs VALUES ($$a;b$$)");
+  }
+}
@@ -0,0 +1,319 @@
+//! Embedding backends: the local fastembed models, from the catalog or a model directory, and
+//! OpenAI-compatible `/v1/embeddings` endpoints, such as a llama.cpp, vLLM or Ollama server. The
+//! embedding pool of the JSON database generator and [`SemanticSearch`](crate::SemanticSearch)
+//! embed text through [`EmbeddingBackend`], so either can be used to build and query a database.
+use anyhow::{Context, Result};
+use fastembed::EmbeddingModel;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokenizers::Tokenizer;
+
+use crate::embeddings_generator::{
+  find_model, max_input_length, model_id, EmbeddingsGenerator, ModelTokenizer, DEFAULT_MODEL,
+};
+use crate::hashing_embedder::{
+  hashing_model_id, parse_hashing_model, HashingEmbedder, HASHING_MAX_INPUT_LENGTH,
+};
+use crate::model_store::ModelManifest;
+
+/// Something that turns text into embedding vectors.
+pub trait EmbeddingBackend: Send {
+  /// Embeds `texts`, returning one vector per text in the same order. `batch_size` is a hint
+  /// of how many texts to hand to the model at once.
+  fn embed(&mut self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>>;
+
+  /// The tokenizer used to size chunks to the model's maximum input length.
+  fn tokenizer(&self) -> Result<ModelTokenizer>;
+
+  /// Identifier of the model, recorded in the database and checked before searching it.
+  fn model_id(&self) -> String;
+
+  /// Embeds a single text, such as a search query.
+  fn embed_one(&mut self, text: &str) -> Result<Vec<f32>> {
+    self
+      .embed(&[text], None)?
+      .into_iter()
+      .next()
+      .ok_or_else(|| anyhow::anyhow!("Failed to generate embedding"))
+  }
+}
+
+impl EmbeddingBackend for EmbeddingsGenerator {
+  fn embed(&mut self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+    self.generate_embeddings(texts.to_vec(), batch_size)
+  }
+
+  fn tokenizer(&self) -> Result<ModelTokenizer> {
+    EmbeddingsGenerator::tokenizer(self)
+  }
+
+  fn model_id(&self) -> String {
+    EmbeddingsGenerator::model_id(self).to_string()
+  }
+}
+
+/// Which embedding backend to use and how to reach it.
+#[derive(Debug, Clone)]
+pub enum EmbeddingBackendConfig {
+  /// A model of the fastembed catalog running in-process
+  FastEmbed(EmbeddingModel),
+  /// A model directory written by `toak model export`, loaded without network access
+  ModelDir(PathBuf),
+  /// An OpenAI-compatible embeddings endpoint
+  OpenAi(OpenAiConfig),
+  /// The deterministic [`HashingEmbedder`] with vectors of this dimension, for tests and
+  /// keyword search without a model
+  Hashing(usize),
+}
+
+impl Default for EmbeddingBackendConfig {
+  fn default() -> Self {
+    Self::FastEmbed(DEFAULT_MODEL)
+  }
+}
+
+impl EmbeddingBackendConfig {
+  /// The local backend of a model identifier recorded in a database or given on the command
+  /// line: a fastembed catalog model, by code or name, or a hashing embedder.
+  pub fn for_model(model: &str) -> Option<Self> {
+    parse_hashing_model(model)
+      .map(Self::Hashing)
+      .or_else(|| find_model(model).map(Self::FastEmbed))
+  }
+
+  /// Creates an instance of the backend. Each instance of a local model loads its own copy.
+  pub fn create(&self) -> Result<Box<dyn EmbeddingBackend>> {
+    match self {
+      Self::FastEmbed(model) => Ok(Box::new(EmbeddingsGenerator::with_model(model.clone())?)),
+      Self::ModelDir(dir) => Ok(Box::new(EmbeddingsGenerator::with_model_dir(dir)?)),
+      Self::OpenAi(config) => Ok(Box::new(OpenAiBackend::new(config.clone())?)),
+      Self::Hashing(dimension) => Ok(Box::new(HashingEmbedder::new(*dimension))),
+    }
+  }
+
+  /// Identifier of the model recorded in the database. Fails if a model directory has no
+  /// readable manifest.
+  pub fn model_id(&self) -> Result<String> {
+    Ok(match self {
+      Self::FastEmbed(model) => model_id(model),
+      Self::ModelDir(dir) => ModelManifest::load(dir)?.model,
+      Self::OpenAi(config) => config.model.clone(),
+      Self::Hashing(dimension) => hashing_model_id(*dimension),
+    })
+  }
+
+  /// Maximum input length of the model in tokens. Fails if a model directory has no readable
+  /// manifest.
+  pub fn max_input_length(&self) -> Result<usize> {
+    Ok(match self {
+      Self::FastEmbed(model) => max_input_length(model),
+      Self::ModelDir(dir) => ModelManifest::load(dir)?.max_length,
+      Self::OpenAi(config) => config.max_input_length,
+      Self::Hashing(_) => HASHING_MAX_INPUT_LENGTH,
+    })
+  }
+
+  /// Number of backend instances to run in parallel: one model per worker for local models,
+  /// or the concurrent request limit for HTTP endpoints. Hashing is fast enough for one.
+  pub fn workers(&self, pool_size: usize) -> usize {
+    match self {
+      Self::FastEmbed(_) | Self::ModelDir(_) => pool_size,
+      Self::OpenAi(config) => config.max_concurrency,
+      Self::Hashing(_) => 1,
+    }
+    .max(1)
+  }
+}
+
+/// Settings of an OpenAI-compatible embeddings endpoint.
+#[derive(Debug, Clone)]
+pub struct OpenAiConfig {
+  /// Base URL of the API, e.g. `http://localhost:8080/v1`; requests go to `{base_url}/embeddings`
+  pub base_url: String,
+  /// Model name sent with each request
+  pub model: String,
+  /// Sent as a bearer token when set
+  pub api_key: Option<String>,
+  /// Texts per request, unless the caller asks for another batch size
+  pub batch_size: usize,
+  /// Requests in flight at once
+  pub max_concurrency: usize,
+  /// Retries of a request that failed with a connection error, 429 or 5xx status
+  pub max_retries: usize,
+  /// Wait before the first retry, doubled for each following one
+  pub retry_backoff: Duration,
+  /// Timeout of each request
+  pub timeout: Duration,
+  /// Maximum input length of the model in tokens
+  pub max_input_length: usize,
+  /// The model's `tokenizer.json`, for exact token counts; lengths are estimated without it
+  pub tokenizer_file: Option<PathBuf>,
+}
+
+impl OpenAiConfig {
+  pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+    Self {
+      base_url: base_url.into(),
+      model: model.into(),
+      api_key: None,
+      batch_size: 64,
+      max_concurrency: 4,
+      max_retries: 3,
+      retry_backoff: Duration::from_millis(500),
+      timeout: Duration::from_secs(60),
+      max_input_length: 8192,
+      tokenizer_file: None,
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct EmbeddingsRequest<'a> {
+  model: &'a str,
+  input: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+  data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+  embedding: Vec<f32>,
+  index: usize,
+}
+
+/// Client of an OpenAI-compatible `/embeddings` endpoint. Texts are sent in batches, and
+/// requests failing with connection errors, rate limits or server errors are retried with
+/// exponential backoff, honoring `Retry-After`.
+pub struct OpenAiBackend {
+  config: OpenAiConfig,
+  agent: ureq::Agent,
+  url: String,
+}
+
+impl OpenAiBackend {
+  /// Creates a client for `config`. Fails if its base URL is not an `http` or `https` URL.
+  pub fn new(config: OpenAiConfig) -> Result<Self> {
+    let host = ["http://", "https://"]
+      .iter()
+      .find_map(|scheme| config.base_url.strip_prefix(scheme))
+      .with_context(|| {
+        format!(
+          "embeddings endpoint {} is not an http or https URL",
+          config.base_url
+        )
+      })?;
+    if host.split('/').next().unwrap_or_default().is_empty() {
+      return Err(anyhow::anyhow!(
+        "embeddings endpoint {} has no host",
+        config.base_url
+      ));
+    }
+    let agent = ureq::AgentBuilder::new().timeout(config.timeout).build();
+    let url = format!("{}/embeddings", config.base_url.trim_end_matches('/'));
+    Ok(Self { config, agent, url })
+  }
+
+  fn request(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+    let body = EmbeddingsRequest {
+      model: &self.config.model,
+      input: texts,
+    };
+    let mut backoff = self.config.retry_backoff;
+    let mut attempt = 0;
+    loop {
+      let mut request = self.agent.post(&self.url);
+      if let Some(api_key) = &self.config.api_key {
+        request = request.set("Authorization", &format!("Bearer {}", api_key));
+      }
+      let error = match request.send_json(&body) {
+        Ok(response) => {
+          let response: EmbeddingsResponse = response
+            .into_json()
+            .with_context(|| format!("invalid embeddings response from {}", self.url))?;
+          return order_embeddings(response, texts.len());
+        }
+        Err(error) => error,
+      };
+
+      let retryable = match &error {
+        ureq::Error::Status(status, _) => *status == 429 || *status >= 500,
+        ureq::Error::Transport(_) => true,
+      };
+      if !retryable || attempt >= self.config.max_retries {
+        return Err(match error {
+          ureq::Error::Status(status, response) => anyhow::anyhow!(
+            "embeddings request to {} failed with status {}: {}",
+            self.url,
+            status,
+            response.into_string().unwrap_or_default().trim()
+          ),
+          error => anyhow::anyhow!("embeddings request to {} failed: {}", self.url, error),
+        });
+      }
+
+      let retry_after = match &error {
+        ureq::Error::Status(_, response) => response
+          .header("Retry-After")
+          .and_then(|seconds| seconds.trim().parse().ok())
+          .map(Duration::from_secs),
+        ureq::Error::Transport(_) => None,
+      };
+      std::thread::sleep(retry_after.unwrap_or(backoff));
+      backoff *= 2;
+      attempt += 1;
+    }
+  }
+}
+
+/// Puts the embeddings of a response in input order; servers may return them in any order.
+fn order_embeddings(response: EmbeddingsResponse, expected: usize) -> Result<Vec<Vec<f32>>> {
+  let mut embeddings = vec![None; expected];
+  for data in response.data {
+    let slot = embeddings.get_mut(data.index).ok_or_else(|| {
+      anyhow::anyhow!(
+        "embeddings response has index {} for {} inputs",
+        data.index,
+        expected
+      )
+    })?;
+    *slot = Some(data.embedding);
+  }
+  embeddings
+    .into_iter()
+    .enumerate()
+    .map(|(index, embedding)| {
+      embedding.ok_or_else(|| anyhow::anyhow!("embeddings response is missing input {}", index))
+    })
+    .collect()
+}
+
+impl EmbeddingBackend for OpenAiBackend {
+  fn embed(&mut self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+    let batch_size = batch_size.unwrap_or(self.config.batch_size).max(1);
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(batch_size) {
+      embeddings.extend(self.request(batch)?);
+    }
+    Ok(embeddings)
+  }
+
+  fn tokenizer(&self) -> Result<ModelTokenizer> {
+    match &self.config.tokenizer_file {
+      Some(path) => {
+        let tokenizer = Tokenizer::from_file(path)
+          .map_err(anyhow::Error::msg)
+          .with_context(|| format!("failed to load tokenizer {}", path.display()))?;
+        ModelTokenizer::with_max_length(&tokenizer, self.config.max_input_length)
+      }
+      None => Ok(ModelTokenizer::estimated(self.config.max_input_length)),
+    }
+  }
+
+  fn model_id(&self) -> String {
+    self.config.model.clone()
+  }
+}
@@ -1,23 +1,157 @@
 //! Utilities for creating semantic embeddings via the `fastembed` crate.
 //! This module powers the embedding generation features that back the JSON database
 //! exporter and any higher level tooling.
-use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
-use anyhow::Result;
+use fastembed::{TextEmbedding, InitOptions, InitOptionsUserDefined, EmbeddingModel};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokenizers::Tokenizer;
+
+use crate::text_chunker::TokenCounter;
+
+/// The model used unless another one is selected.
+pub const DEFAULT_MODEL: EmbeddingModel = EmbeddingModel::EmbeddingGemma300M;
+
+/// Looks up a model of the fastembed catalog by its code, e.g. `Xenova/bge-small-en-v1.5`, or by
+/// its variant name, e.g. `BGESmallENV15`, ignoring case.
+pub fn find_model(name: &str) -> Option<EmbeddingModel> {
+    TextEmbedding::list_supported_models()
+        .into_iter()
+        .find(|info| info.model_code.eq_ignore_ascii_case(name) || format!("{:?}", info.model).eq_ignore_ascii_case(name))
+        .map(|info| info.model)
+}
+
+/// The catalog code of `model`, which identifies it in embeddings databases.
+pub fn model_id(model: &EmbeddingModel) -> String {
+    TextEmbedding::get_model_info(model).map_or_else(|_| format!("{:?}", model), |info| info.model_code.clone())
+}
+
+/// Whether two model identifiers name the same model. Catalog models match by code or variant
+/// name, so databases that recorded the variant name still match; other names must be equal.
+pub fn is_same_model(a: &str, b: &str) -> bool {
+    match (find_model(a), find_model(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Maximum input length of an embedding model in tokens, special tokens included, from its
+/// model card. Longer input is truncated by the model.
+pub fn max_input_length(model: &EmbeddingModel) -> usize {
+    match model {
+        EmbeddingModel::NomicEmbedTextV1
+        | EmbeddingModel::NomicEmbedTextV15
+        | EmbeddingModel::NomicEmbedTextV15Q
+        | EmbeddingModel::ModernBertEmbedLarge
+        | EmbeddingModel::GTEBaseENV15
+        | EmbeddingModel::GTEBaseENV15Q
+        | EmbeddingModel::GTELargeENV15
+        | EmbeddingModel::GTELargeENV15Q
+        | EmbeddingModel::JinaEmbeddingsV2BaseCode => 8192,
+        EmbeddingModel::EmbeddingGemma300M => 2048,
+        EmbeddingModel::ClipVitB32 => 77,
+        // BERT-style models: MiniLM, BGE, E5, mpnet, mxbai
+        _ => 512,
+    }
+}
+
+/// Bytes per token assumed when the model's tokenizer is unknown; BPE vocabularies average
+/// about four bytes per token on code, so assuming three errs on the side of smaller chunks.
+const ESTIMATED_BYTES_PER_TOKEN: usize = 3;
+
+fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(ESTIMATED_BYTES_PER_TOKEN)
+}
+
+#[derive(Clone)]
+enum TokenizerKind {
+    Model(Box<Tokenizer>),
+    Estimate,
+}
+
+/// The tokenizer of an embedding model, used to size chunks in the model's own tokens.
+#[derive(Clone)]
+pub struct ModelTokenizer {
+    tokenizer: TokenizerKind,
+    max_length: usize,
+    special_tokens: usize,
+}
+
+impl ModelTokenizer {
+    /// Wraps the tokenizer of a loaded model, whose truncation length is its maximum input length.
+    pub fn new(tokenizer: &Tokenizer) -> Result<Self> {
+        let max_length = tokenizer.get_truncation().map_or(usize::MAX, |truncation| truncation.max_length);
+        Self::with_max_length(tokenizer, max_length)
+    }
+
+    /// Wraps a tokenizer, such as one loaded from a model's `tokenizer.json`, for a model that
+    /// reads at most `max_length` tokens.
+    pub fn with_max_length(tokenizer: &Tokenizer, max_length: usize) -> Result<Self> {
+        let mut tokenizer = tokenizer.clone();
+        tokenizer.with_truncation(None).map_err(anyhow::Error::msg)?;
+        tokenizer.with_padding(None);
+        let special_tokens = tokenizer.encode("", true).map_err(anyhow::Error::msg)?.len();
+        Ok(Self {
+            tokenizer: TokenizerKind::Model(Box::new(tokenizer)),
+            max_length,
+            special_tokens,
+        })
+    }
+
+    /// Estimates token counts from the length of the text, for models whose tokenizer is not
+    /// available locally, such as ones behind an HTTP API.
+    pub fn estimated(max_length: usize) -> Self {
+        Self {
+            tokenizer: TokenizerKind::Estimate,
+            max_length,
+            special_tokens: 0,
+        }
+    }
+
+    /// Maximum number of tokens the model reads, special tokens included.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Tokens left for chunk content once the model's special tokens are accounted for.
+    pub fn content_budget(&self) -> usize {
+        self.max_length.saturating_sub(self.special_tokens)
+    }
+
+    /// Number of tokens the model would be given for `text`, before truncation.
+    pub fn input_length(&self, text: &str) -> usize {
+        self.count_tokens(text) + self.special_tokens
+    }
+}
+
+impl TokenCounter for ModelTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        match &self.tokenizer {
+            // Text the tokenizer rejects is still embedded, so it must not count as empty
+            TokenizerKind::Model(tokenizer) => tokenizer
+                .encode(text, false)
+                .map_or_else(|_| estimate_tokens(text), |encoding| encoding.len()),
+            TokenizerKind::Estimate => estimate_tokens(text),
+        }
+    }
+}
 
 /// A builder around `fastembed::TextEmbedding` that exposes simple helpers
 /// for generating per-text or batch embeddings.
 pub struct EmbeddingsGenerator {
     model: TextEmbedding,
+    model_id: String,
 }
 
 impl EmbeddingsGenerator {
     /// Creates a new embeddings generator with the default model
     pub fn new() -> Result<Self> {
-        Self::with_model(EmbeddingModel::EmbeddingGemma300M)
+        Self::with_model(DEFAULT_MODEL)
     }
 
     /// Creates a new embeddings generator with a specific model
     pub fn with_model(model: EmbeddingModel) -> Result<Self> {
+        let model_id = model_id(&model);
         // Log the platform/backend hints to help validate acceleration on Apple Silicon.
         #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
         {
@@ -33,7 +167,8 @@ impl EmbeddingsGenerator {
         #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
         let text_embedding = {
             let try_init = |m: EmbeddingModel| {
-                TextEmbedding::try_new(InitOptions::new(m).with_show_download_progress(true))
+                let max_length = max_input_length(&m);
+                TextEmbedding::try_new(InitOptions::new(m).with_max_length(max_length).with_show_download_progress(true))
             };
             match try_init(model.clone()) {
                 Ok(ok) => {
@@ -58,14 +193,59 @@ impl EmbeddingsGenerator {
 
         #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
         let text_embedding = TextEmbedding::try_new(
-            InitOptions::new(model).with_show_download_progress(true),
+            InitOptions::new(model.clone())
+                .with_max_length(max_input_length(&model))
+                .with_show_download_progress(true),
         )?;
 
         Ok(Self {
             model: text_embedding,
+            model_id,
         })
     }
 
+    /// Creates an embeddings generator from a model directory written by `toak model export`,
+    /// without downloading anything.
+    ///
+    /// ONNX Runtime resolves the external data of a model loaded from memory against the working
+    /// directory, so for models with external data the process's working directory is switched to
+    /// the model's directory while the session is built, and back even if building fails or
+    /// panics. This is process-wide: loads through this function are serialized, but other
+    /// threads resolving relative paths meanwhile are affected.
+    pub fn with_model_dir(dir: &Path) -> Result<Self> {
+        static WORKING_DIR: Mutex<()> = Mutex::new(());
+        // Resolved up front, and read under the lock, so a concurrent switch cannot redirect it
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("model directory {} not found", dir.display()))?;
+        let _guard = WORKING_DIR.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (manifest, model) = crate::model_store::read_model_dir(&dir)?;
+        let options = InitOptionsUserDefined::new().with_max_length(manifest.max_length);
+        let text_embedding = if manifest.external_data.is_empty() {
+            TextEmbedding::try_new_from_user_defined(model, options)?
+        } else {
+            let model_dir = dir.join(&manifest.model_file).parent().map(Path::to_path_buf).unwrap_or_default();
+            let _restore = RestoreWorkingDir(std::env::current_dir()?);
+            std::env::set_current_dir(&model_dir)?;
+            TextEmbedding::try_new_from_user_defined(model, options)?
+        };
+
+        Ok(Self {
+            model: text_embedding,
+            model_id: manifest.model,
+        })
+    }
+
+    /// Catalog code of the model, e.g. `google/embeddinggemma-300m`
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    /// The model's tokenizer, for sizing chunks to its maximum input length
+    pub fn tokenizer(&self) -> Result<ModelTokenizer> {
+        ModelTokenizer::new(&self.model.tokenizer)
+    }
+
     /// Generates embeddings for a batch of texts
     /// The `batch_size` parameter can be used to control memory usage and throughput.
     pub fn generate_embeddings(&mut self, texts: Vec<&str>, batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
@@ -80,3 +260,13 @@ impl EmbeddingsGenerator {
             .ok_or_else(|| anyhow::anyhow!("Failed to generate embedding"))
     }
 }
+
+/// Switches the working directory back when dropped, so a failed or panicking session build
+/// cannot leave the process in the model's directory.
+struct RestoreWorkingDir(PathBuf);
+
+impl Drop for RestoreWorkingDir {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.0);
+    }
+}
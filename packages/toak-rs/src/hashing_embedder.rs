@@ -0,0 +1,168 @@
+//! A deterministic embedding backend that needs no model: words, word pairs and character
+//! trigrams are hashed into a fixed number of signed buckets. Texts sharing vocabulary land
+//! close together, which is enough to test the generate → search pipeline hermetically and to
+//! search code by keyword on machines without any model.
+use anyhow::Result;
+
+use crate::embedding_backend::EmbeddingBackend;
+use crate::embeddings_generator::ModelTokenizer;
+
+/// Model identifiers of hashing embedders start with this, followed by their dimension.
+pub const HASHING_MODEL_PREFIX: &str = "toak/hashing-";
+
+/// Dimension used unless another one is chosen.
+pub const DEFAULT_HASHING_DIMENSION: usize = 256;
+
+/// Largest dimension accepted in a model identifier, keeping vectors and databases a sane size.
+pub const MAX_HASHING_DIMENSION: usize = 16384;
+
+/// Input length reported to the chunker; hashing has no limit of its own.
+pub const HASHING_MAX_INPUT_LENGTH: usize = 8192;
+
+/// Model identifier of a hashing embedder with `dimension` buckets, e.g. `toak/hashing-256`.
+pub fn hashing_model_id(dimension: usize) -> String {
+  format!("{}{}", HASHING_MODEL_PREFIX, dimension)
+}
+
+/// The dimension of a hashing embedder's model identifier, or `None` for other models and for
+/// dimensions outside `1..=MAX_HASHING_DIMENSION`.
+pub fn parse_hashing_model(model: &str) -> Option<usize> {
+  model
+    .strip_prefix(HASHING_MODEL_PREFIX)?
+    .parse()
+    .ok()
+    .filter(|dimension| (1..=MAX_HASHING_DIMENSION).contains(dimension))
+}
+
+/// Embeds text by feature hashing. The same text always yields the same unit-length vector,
+/// on every platform and version.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+  dimension: usize,
+}
+
+impl HashingEmbedder {
+  pub fn new(dimension: usize) -> Self {
+    Self {
+      dimension: dimension.max(1),
+    }
+  }
+
+  /// Length of the embedding vectors.
+  pub fn dimension(&self) -> usize {
+    self.dimension
+  }
+
+  /// Embeds a single text.
+  pub fn embed_text(&self, text: &str) -> Vec<f32> {
+    let mut embedding = vec![0.0; self.dimension];
+    let words: Vec<String> = text
+      .split(|c: char| !c.is_alphanumeric())
+      .filter(|word| !word.is_empty())
+      .map(str::to_lowercase)
+      .collect();
+
+    for word in &words {
+      self.add_feature(&mut embedding, &["w", word], 1.0);
+      // Trigrams of the padded word match inflections and identifier fragments
+      let chars: Vec<char> = format!("^{}$", word).chars().collect();
+      for trigram in chars.windows(3) {
+        self.add_feature(
+          &mut embedding,
+          &["c", &trigram.iter().collect::<String>()],
+          0.25,
+        );
+      }
+    }
+    for pair in words.windows(2) {
+      self.add_feature(&mut embedding, &["p", &pair[0], &pair[1]], 0.5);
+    }
+    // Text without words, such as punctuation or whitespace, is hashed whole so that its
+    // vector still has unit length
+    if words.is_empty() {
+      self.add_feature(&mut embedding, &["t", text], 1.0);
+    }
+
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+      embedding.iter_mut().for_each(|x| *x /= norm);
+    }
+    embedding
+  }
+
+  fn add_feature(&self, embedding: &mut [f32], parts: &[&str], weight: f32) {
+    let hash = fnv1a(parts);
+    let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+    embedding[(hash % self.dimension as u64) as usize] += sign * weight;
+  }
+}
+
+/// 64-bit FNV-1a of `parts`, separated so that `["ab", "c"]` and `["a", "bc"]` differ. Unlike
+/// the standard library's hasher, it is stable across Rust versions.
+fn fnv1a(parts: &[&str]) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+  for part in parts {
+    for byte in part.bytes().chain(std::iter::once(0xff)) {
+      hash ^= byte as u64;
+      hash = hash.wrapping_mul(0x100000001b3);
+    }
+  }
+  hash
+}
+
+impl EmbeddingBackend for HashingEmbedder {
+  fn embed(&mut self, texts: &[&str], _batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+    Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+  }
+
+  fn tokenizer(&self) -> Result<ModelTokenizer> {
+    Ok(ModelTokenizer::estimated(HASHING_MAX_INPUT_LENGTH))
+  }
+
+  fn model_id(&self) -> String {
+    hashing_model_id(self.dimension)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+  }
+
+  #[test]
+  fn test_hashing_embeddings_are_deterministic_and_ordered() {
+    let embedder = HashingEmbedder::new(DEFAULT_HASHING_DIMENSION);
+    let query = embedder.embed_text("parse the config file");
+    assert_eq!(query, embedder.embed_text("parse the config file"));
+    assert_eq!(query.len(), 256);
+    assert!((similarity(&query, &query) - 1.0).abs() < 1e-5);
+
+    let related = embedder.embed_text("fn parse_config(file: &Path) -> Config");
+    let unrelated = embedder.embed_text("render the widget on screen");
+    assert!(similarity(&query, &related) > similarity(&query, &unrelated));
+
+    assert_eq!(parse_hashing_model(&hashing_model_id(64)), Some(64));
+    assert_eq!(parse_hashing_model("toak/hashing-0"), None);
+    assert_eq!(
+      parse_hashing_model(&hashing_model_id(MAX_HASHING_DIMENSION + 1)),
+      None
+    );
+    assert_eq!(parse_hashing_model("Xenova/bge-small-en-v1.5"), None);
+  }
+
+  #[test]
+  fn test_text_without_words_has_unit_length() {
+    let embedder = HashingEmbedder::new(64);
+    for text in ["", "  ", "=> {}", "-- ;;"] {
+      assert!(
+        (similarity(&embedder.embed_text(text), &embedder.embed_text(text)) - 1.0).abs() < 1e-5,
+        "{:?}",
+        text
+      );
+    }
+    assert_ne!(embedder.embed_text("=> {}"), embedder.embed_text("-- ;;"));
+  }
+}
@@ -1,11 +1,22 @@
 //! Helpers that walk a git repository, chunk the code, and persist embeddings into a JSON database.
-use crate::embeddings_generator::EmbeddingsGenerator;
-use crate::text_chunker::{chunk_text, ChunkerConfig};
-use crate::token_cleaner::clean_and_redact;
+use crate::chunk_context::{context_header, embedding_input};
+use crate::embedding_backend::EmbeddingBackendConfig;
+use crate::embeddings_generator::ModelTokenizer;
+use crate::license_headers::HeaderIndex;
+use crate::data_summarizer::{summarize_data_file, DataSummaryOptions};
+use crate::lockfiles::{DependencySummary, LockfileKind};
+use crate::markdown_generator::is_default_exclusion;
+use crate::notebook::{extract_notebook_lines, is_notebook, NotebookOptions};
+use crate::source_map::{LineIndex, SourceMap, SourceSpan};
+use crate::text_chunker::{chunk_file, chunk_text, ChunkerConfig, TextChunk};
+use crate::token_cleaner::{
+    clean_and_redact_with_options, CleaningOptions, CommentMode, FingerprintKey, StrippingOptions,
+};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
@@ -23,8 +34,27 @@ pub struct ChunkMetadata {
     pub total_chunks: usize,
     pub file_size: u64,
     pub last_modified: Option<String>,
+    /// First line (0-based) of the chunk in the cleaned file content
     pub start_index: usize,
+    /// Line after the chunk (exclusive) in the cleaned file content
     pub end_index: usize,
+    /// Where the chunk came from in the original file, whole lines for notebook cells; absent for
+    /// generated content such as data file and dependency summaries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<SourceSpan>,
+    /// Section headings enclosing the chunk in Markdown, reStructuredText and AsciiDoc files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading_path: Option<String>,
+    /// Signatures of the code items enclosing the chunk, for syntax-chunked source files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+/// Another place a chunk's content occurs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkLocation {
+    pub file_path: String,
+    pub metadata: ChunkMetadata,
 }
 
 /// A chunk of file content with its embedding
@@ -32,8 +62,17 @@ pub struct ChunkMetadata {
 pub struct EmbeddedChunk {
     pub file_path: String,
     pub content: String,
+    /// SHA-256 of the cleaned content, in hex
+    #[serde(default)]
+    pub content_hash: String,
+    /// Context header embedded ahead of the content; not part of the displayed content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
     pub embedding: Vec<f32>,
     pub metadata: ChunkMetadata,
+    /// Other locations with the same content, which share this chunk's embedding
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<ChunkLocation>,
 }
 
 /// A chunk staged for embedding (no vector yet)
@@ -41,19 +80,111 @@ pub struct EmbeddedChunk {
 struct PendingChunk {
     file_path: String,
     content: String,
+    context: Option<String>,
     metadata: ChunkMetadata,
 }
 
+/// A unique chunk staged for embedding and the other locations of its content
+#[derive(Debug)]
+struct UniqueChunk {
+    chunk: PendingChunk,
+    content_hash: String,
+    duplicates: Vec<ChunkLocation>,
+}
+
+/// Tolerance on the length of vectors considered normalized
+const UNIT_LENGTH_TOLERANCE: f32 = 1e-3;
+
+/// Whether `vector` has unit length, within `UNIT_LENGTH_TOLERANCE`.
+pub(crate) fn is_unit_length(vector: &[f32]) -> bool {
+    (vector.iter().map(|x| x * x).sum::<f32>().sqrt() - 1.0).abs() < UNIT_LENGTH_TOLERANCE
+}
+
+/// The common length of `embeddings`, and whether they all have unit length. Fails if the
+/// backend returned vectors of different lengths.
+fn vector_shape(embeddings: &[Vec<f32>]) -> Result<(usize, bool)> {
+    let dimension = embeddings.first().map_or(0, Vec::len);
+    if let Some(other) = embeddings.iter().find(|embedding| embedding.len() != dimension) {
+        return Err(anyhow::anyhow!(
+            "embedding backend returned vectors of {} and {} dimensions",
+            dimension,
+            other.len()
+        ));
+    }
+    let normalized = embeddings.iter().all(|embedding| is_unit_length(embedding));
+    Ok((dimension, normalized))
+}
+
+/// SHA-256 of `content`, in hex
+fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Groups chunks with the same content, in order of first occurrence. The first occurrence is
+/// embedded and the others are recorded as its duplicates.
+fn deduplicate(chunks: Vec<PendingChunk>) -> Vec<UniqueChunk> {
+    let mut unique: Vec<UniqueChunk> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for chunk in chunks {
+        let content_hash = content_hash(&chunk.content);
+        match positions.get(&content_hash) {
+            Some(&position) => unique[position].duplicates.push(ChunkLocation {
+                file_path: chunk.file_path,
+                metadata: chunk.metadata,
+            }),
+            None => {
+                positions.insert(content_hash.clone(), unique.len());
+                unique.push(UniqueChunk { chunk, content_hash, duplicates: Vec::new() });
+            }
+        }
+    }
+    unique
+}
+
+/// Gives each chunk the context header of its location. A header names a single file, so content
+/// found in several files is embedded without one; the item or section line is left out when it
+/// would push a chunk past the model's maximum input length.
+fn add_context_headers(chunks: &mut [UniqueChunk], tokenizer: &ModelTokenizer) {
+    for unique in chunks {
+        let chunk = &mut unique.chunk;
+        if unique.duplicates.iter().any(|location| location.file_path != chunk.file_path) {
+            continue;
+        }
+        let metadata = &chunk.metadata;
+        let header = context_header(&chunk.file_path, metadata.parent.as_deref(), metadata.heading_path.as_deref());
+        let fits = tokenizer.input_length(&embedding_input(Some(&header), &chunk.content)) <= tokenizer.max_length();
+        chunk.context = Some(if fits { header } else { context_header(&chunk.file_path, None, None) });
+    }
+}
+
+impl PendingChunk {
+    /// The text handed to the embedding model
+    fn embedding_input(&self) -> String {
+        embedding_input(self.context.as_deref(), &self.content)
+    }
+}
+
 /// The complete JSON database structure
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EmbeddingsDatabase {
     pub version: String,
     pub generated_at: String,
+    /// Identifier of the embedding model, e.g. `google/embeddinggemma-300m`
     pub model: String,
+    /// Length of the embedding vectors; absent when there are none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<usize>,
+    /// Whether the embedding vectors have unit length
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<bool>,
     pub chunk_size: usize,
     pub overlap_size: usize,
     pub total_files: usize,
+    /// Unique chunks, each with its own embedding
     pub total_chunks: usize,
+    /// Places chunk content occurs, counting the duplicates of unique chunks
+    #[serde(default)]
+    pub total_locations: usize,
     pub chunks: Vec<EmbeddedChunk>,
 }
 
@@ -67,10 +198,26 @@ pub struct JsonDatabaseOptions {
     pub chunker_config: ChunkerConfig,
     /// Maximum number of files to process concurrently
     pub max_concurrent_files: usize,
-    /// Number of parallel embedding workers (each maintains its own model instance)
+    /// Which model embeds the chunks: a local fastembed model or an OpenAI-compatible endpoint
+    pub embedding_backend: EmbeddingBackendConfig,
+    /// Number of parallel embedding workers (each maintains its own model instance); HTTP
+    /// backends use their own concurrency limit instead
     pub embedding_pool_size: usize,
     /// Optional batch size hint passed to the embedding backend
     pub embedding_batch_size: Option<usize>,
+    /// How file content is sanitized, redacted and cleaned before chunking; documentation
+    /// comments are kept by default
+    pub cleaning: CleaningOptions,
+    /// Remove license headers and banners repeated across files before chunking
+    pub strip_boilerplate_headers: bool,
+    /// Replace large JSON, CSV, YAML and SQL files by their structure and a few sample records
+    /// before chunking; `None` chunks them whole
+    pub data_summary: Option<DataSummaryOptions>,
+    /// Embed a dependency summary of each lockfile as its own chunk instead of the raw lockfile
+    pub dependency_summary: bool,
+    /// Prepend a header with the file path, language and enclosing item or section to each
+    /// chunk's embedding input; the header is stored apart from the chunk content
+    pub context_headers: bool,
 }
 
 impl Default for JsonDatabaseOptions {
@@ -80,7 +227,7 @@ impl Default for JsonDatabaseOptions {
         let cpu_count = std::thread::available_parallelism()
             .map(|n| n.get())
             .unwrap_or(4);
-        let default_pool = cpu_count.min(4).max(1);
+        let default_pool = cpu_count.clamp(1, 4);
 
         Self {
             dir: PathBuf::from("."),
@@ -90,8 +237,23 @@ impl Default for JsonDatabaseOptions {
             verbose: true,
             chunker_config: ChunkerConfig::default(),
             max_concurrent_files: 4,
+            embedding_backend: EmbeddingBackendConfig::default(),
             embedding_pool_size: default_pool,
             embedding_batch_size: None,
+            // Doc comments are the most useful text for semantic search, and document sections
+            // are chunked by their headings and blank lines
+            cleaning: CleaningOptions {
+                stripping: StrippingOptions {
+                    comments: CommentMode::KeepDocs,
+                    keep_prose: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            strip_boilerplate_headers: true,
+            data_summary: Some(DataSummaryOptions::default()),
+            dependency_summary: true,
+            context_headers: true,
         }
     }
 }
@@ -103,11 +265,36 @@ pub struct JsonDatabaseGenerator {
 }
 
 impl JsonDatabaseGenerator {
-    /// Creates a new JSON database generator
-    pub fn new(options: JsonDatabaseOptions) -> Result<Self> {
+    /// Creates a new JSON database generator. Chunks are sized with the embedding model's
+    /// tokenizer unless the chunker config brings its own, and a chunk size over the model's
+    /// maximum input length is lowered to fit it. The project's secret allowlist, baseline and
+    /// fingerprint key are loaded from `options.dir`, without writing to it.
+    pub fn new(mut options: JsonDatabaseOptions) -> Result<Self> {
+        options.cleaning.redaction.load_project_files(&options.dir)?;
+
         // Build a pool of embedding workers that each own their model instance.
         // Workers live on dedicated threads and communicate via channels — no mutex around the model.
-        let embeddings_pool = EmbeddingPool::new(options.embedding_pool_size)?;
+        let workers = options.embedding_backend.workers(options.embedding_pool_size);
+        let embeddings_pool = EmbeddingPool::new(&options.embedding_backend, workers)?;
+
+        // Smaller models read fewer tokens than the default chunk size
+        let tokenizer = &embeddings_pool.0.tokenizer;
+        let config = &mut options.chunker_config;
+        if config.chunk_size > tokenizer.content_budget() {
+            if options.verbose {
+                println!(
+                    "Chunk size lowered from {} to {} tokens to fit the embedding model's maximum input length of {} tokens",
+                    config.chunk_size,
+                    tokenizer.content_budget(),
+                    tokenizer.max_length()
+                );
+            }
+            config.chunk_size = tokenizer.content_budget().max(1);
+            config.overlap_size = config.overlap_size.min(config.chunk_size / 2);
+        }
+        if options.chunker_config.tokenizer.is_none() {
+            options.chunker_config.tokenizer = Some(Arc::new(tokenizer.clone()));
+        }
 
         Ok(Self {
             options,
@@ -120,8 +307,8 @@ impl JsonDatabaseGenerator {
         self.get_tracked_files_internal().await
     }
 
-    async fn get_tracked_files_internal(&self) -> Result<Vec<String>> {
-        // Run git ls-files
+    /// Lists every file tracked by git
+    fn git_ls_files(&self) -> Result<Vec<String>> {
         let output = Command::new("git")
             .arg("ls-files")
             .current_dir(&self.options.dir)
@@ -138,6 +325,12 @@ impl JsonDatabaseGenerator {
             .map(|s| s.to_string())
             .collect();
 
+        Ok(tracked_files)
+    }
+
+    async fn get_tracked_files_internal(&self) -> Result<Vec<String>> {
+        let tracked_files = self.git_ls_files()?;
+
         if self.options.verbose {
             println!("Total tracked files: {}", tracked_files.len());
         }
@@ -148,20 +341,9 @@ impl JsonDatabaseGenerator {
         let filtered_files = tracked_files
             .into_iter()
             .filter(|file| {
-                let path = Path::new(file);
-                let ext = path
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .map(|e| format!(".{}", e))
-                    .unwrap_or_default();
-
-                // Check if file type is excluded
-                if self.options.file_type_exclusions.contains(&ext) {
-                    return false;
-                }
-
-                // Check if file matches exclusion patterns
-                !self.matches_exclusion_patterns(file)
+                // Lockfiles are embedded as dependency summaries
+                let summarized = self.options.dependency_summary && LockfileKind::from_path(Path::new(file)).is_some();
+                !summarized && !self.is_excluded(file)
             })
             .collect::<Vec<_>>();
 
@@ -176,6 +358,38 @@ impl JsonDatabaseGenerator {
         Ok(filtered_files)
     }
 
+    /// Checks if a tracked file is excluded by its type or the exclusion patterns.
+    fn is_excluded(&self, file: &str) -> bool {
+        let path = Path::new(file);
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+
+        // Fingerprints could be confirmed by anyone holding the key
+        self.options.file_type_exclusions.contains(&ext)
+            || path.ends_with(FingerprintKey::KEY_FILE)
+            || self.matches_exclusion_patterns(file)
+    }
+
+    /// Checks if a lockfile's dependency summary is excluded, by exclusions other than the
+    /// built-in ones.
+    fn is_lockfile_excluded(&self, file: &str) -> bool {
+        let ext = Path::new(file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+
+        (self.options.file_type_exclusions.contains(&ext) && !is_default_exclusion(&ext))
+            || self
+                .options
+                .file_exclusions
+                .iter()
+                .any(|pattern| !is_default_exclusion(pattern) && self.glob_match(pattern, file))
+    }
+
     fn matches_exclusion_patterns(&self, file: &str) -> bool {
         for pattern in &self.options.file_exclusions {
             if self.glob_match(pattern, file) {
@@ -213,6 +427,13 @@ impl JsonDatabaseGenerator {
         // Create a semaphore to limit concurrent file processing
         let semaphore = Arc::new(Semaphore::new(self.options.max_concurrent_files));
 
+        // Headers shared across files would otherwise become many identical chunks
+        let header_index = if self.options.strip_boilerplate_headers {
+            Some(Arc::new(HeaderIndex::from_files(&self.options.dir, &tracked_files).await))
+        } else {
+            None
+        };
+
         // Stage chunks from files concurrently (no embedding yet)
         let stage_start = Instant::now();
         let mut tasks = Vec::new();
@@ -220,7 +441,10 @@ impl JsonDatabaseGenerator {
             let absolute_path = self.options.dir.join(file);
             let file = file.clone();
             let semaphore = semaphore.clone();
-            let chunker_config = self.options.chunker_config.clone();
+            let chunker_config = reserve_header(&self.options.chunker_config, &file, self.header_budget());
+            let cleaning = self.options.cleaning.clone();
+            let header_index = header_index.clone();
+            let data_summary = self.options.data_summary.clone();
             let verbose = self.options.verbose;
             let total_files = tracked_files.len();
 
@@ -232,7 +456,17 @@ impl JsonDatabaseGenerator {
                     println!("Processing file {}/{}: {}", file_idx + 1, total_files, file);
                 }
 
-                match Self::process_file_stage_chunks(&absolute_path, &file, &chunker_config, verbose).await {
+                match Self::process_file_stage_chunks(
+                    &absolute_path,
+                    &file,
+                    &chunker_config,
+                    &cleaning,
+                    header_index.as_deref(),
+                    data_summary.as_ref(),
+                    verbose,
+                )
+                .await
+                {
                     Ok(chunks) => Ok(chunks),
                     Err(e) => {
                         if verbose {
@@ -264,9 +498,39 @@ impl JsonDatabaseGenerator {
             }
         }
 
+        if self.options.dependency_summary {
+            pending_chunks.extend(self.stage_dependency_chunks().await?);
+        }
+
+        // Vendored copies, generated twins and overlaps yield identical chunks, embedded only once
+        let total_locations = pending_chunks.len();
+        let mut unique_chunks = deduplicate(pending_chunks);
+        let duplicate_chunks = total_locations - unique_chunks.len();
+        if self.options.verbose && duplicate_chunks > 0 {
+            println!("{} chunks duplicate the content of others and share their embeddings", duplicate_chunks);
+        }
+
+        // Chunks were sized to leave room for the file and language lines of their header
+        if self.options.context_headers {
+            add_context_headers(&mut unique_chunks, &self.embeddings_pool.0.tokenizer);
+        }
+
         let stage_elapsed = stage_start.elapsed();
-        let total_chunks_count = pending_chunks.len();
-        let staged_bytes: usize = pending_chunks.iter().map(|c| c.content.len()).sum();
+        let total_chunks_count = unique_chunks.len();
+        // Unsplittable content, such as long code blocks and dependency summaries, can exceed the budget
+        let tokenizer = &self.embeddings_pool.0.tokenizer;
+        let truncated_chunks = unique_chunks
+            .iter()
+            .filter(|unique| tokenizer.input_length(&unique.chunk.embedding_input()) > tokenizer.max_length())
+            .count();
+        if self.options.verbose && truncated_chunks > 0 {
+            println!(
+                "{} chunks exceed the embedding model's maximum input length of {} tokens and will be truncated",
+                truncated_chunks,
+                tokenizer.max_length()
+            );
+        }
+        let staged_bytes: usize = unique_chunks.iter().map(|unique| unique.chunk.content.len()).sum();
 
         if self.options.verbose {
             let secs = stage_elapsed.as_secs_f64().max(1e-9);
@@ -285,16 +549,26 @@ impl JsonDatabaseGenerator {
             let database = EmbeddingsDatabase {
                 version: "1.0".to_string(),
                 generated_at: Utc::now().to_rfc3339(),
-                model: "EmbeddingGemma300M".to_string(),
+                model: self.options.embedding_backend.model_id()?,
+                dimension: None,
+                normalized: None,
                 chunk_size: self.options.chunker_config.chunk_size,
                 overlap_size: self.options.chunker_config.overlap_size,
                 total_files: tracked_files.len(),
                 total_chunks: 0,
+                total_locations: 0,
                 chunks: vec![],
             };
             let json = serde_json::to_string_pretty(&database)?;
             fs::write(&self.options.output_file_path, json).await?;
-            return Ok(JsonDatabaseResult { success: true, total_files: tracked_files.len(), total_chunks: 0 });
+            return Ok(JsonDatabaseResult {
+                success: true,
+                total_files: tracked_files.len(),
+                total_chunks: 0,
+                total_locations: 0,
+                truncated_chunks: 0,
+                duplicate_chunks: 0,
+            });
         }
 
         if self.options.verbose {
@@ -302,7 +576,7 @@ impl JsonDatabaseGenerator {
         }
 
         // Build documents list
-        let documents: Vec<String> = pending_chunks.iter().map(|pc| pc.content.clone()).collect();
+        let documents: Vec<String> = unique_chunks.iter().map(|unique| unique.chunk.embedding_input()).collect();
 
         // Perform global batched embedding across the pool
         let embed_start = Instant::now();
@@ -319,6 +593,7 @@ impl JsonDatabaseGenerator {
             .embed_many_ordered(documents, Some(per_job_batch), backend_batch_size)
             .await?;
         let embed_elapsed = embed_start.elapsed();
+        let (dimension, normalized) = vector_shape(&embeddings)?;
         if self.options.verbose {
             let secs = embed_elapsed.as_secs_f64().max(1e-9);
             let chunks_per_sec = total_chunks_count as f64 / secs;
@@ -330,15 +605,19 @@ impl JsonDatabaseGenerator {
 
         // Zip back into embedded chunks
         let mut all_chunks: Vec<EmbeddedChunk> = Vec::with_capacity(total_chunks_count);
-        for (i, pending) in pending_chunks.into_iter().enumerate() {
+        for (i, unique) in unique_chunks.into_iter().enumerate() {
             let embedding = embeddings.get(i)
                 .cloned()
                 .ok_or_else(|| anyhow::anyhow!("missing embedding for chunk {}", i))?;
+            let pending = unique.chunk;
             all_chunks.push(EmbeddedChunk {
                 file_path: pending.file_path,
                 content: pending.content,
+                content_hash: unique.content_hash,
+                context: pending.context,
                 embedding,
                 metadata: pending.metadata,
+                duplicates: unique.duplicates,
             });
         }
 
@@ -349,11 +628,14 @@ impl JsonDatabaseGenerator {
         let database = EmbeddingsDatabase {
             version: "1.0".to_string(),
             generated_at: Utc::now().to_rfc3339(),
-            model: "EmbeddingGemma300M".to_string(),
+            model: self.options.embedding_backend.model_id()?,
+            dimension: Some(dimension),
+            normalized: Some(normalized),
             chunk_size: self.options.chunker_config.chunk_size,
             overlap_size: self.options.chunker_config.overlap_size,
             total_files: tracked_files.len(),
             total_chunks: all_chunks.len(),
+            total_locations,
             chunks: all_chunks,
         };
 
@@ -391,19 +673,81 @@ impl JsonDatabaseGenerator {
             success: true,
             total_files: tracked_files.len(),
             total_chunks: database.total_chunks,
+            total_locations,
+            truncated_chunks,
+            duplicate_chunks,
         })
     }
 
+    /// Tokens left for a chunk and its context header, if context headers are embedded.
+    fn header_budget(&self) -> Option<usize> {
+        self.options.context_headers.then(|| self.embeddings_pool.0.tokenizer.content_budget())
+    }
+
+    /// Stages the dependency summary of each tracked lockfile that is not excluded, cleaned and
+    /// chunked like file contents.
+    async fn stage_dependency_chunks(&self) -> Result<Vec<PendingChunk>> {
+        let mut pending = Vec::new();
+        let mut lockfiles = 0;
+        for file in self.git_ls_files()? {
+            if LockfileKind::from_path(Path::new(&file)).is_none() || self.is_lockfile_excluded(&file) {
+                continue;
+            }
+            let summary = match DependencySummary::load(&self.options.dir, &file).await {
+                Ok(summary) => summary,
+                Err(e) => {
+                    if self.options.verbose {
+                        eprintln!("Error summarizing lockfile {}: {}", file, e);
+                    }
+                    continue;
+                }
+            };
+            let (content, _) = clean_and_redact_with_options(&summary.to_markdown(), &file, &self.options.cleaning);
+            let metadata = fs::metadata(self.options.dir.join(&file)).await?;
+            let last_modified = metadata.modified().ok().map(|time| DateTime::<Utc>::from(time).to_rfc3339());
+            let chunker_config = reserve_header(&self.options.chunker_config, &file, self.header_budget());
+            let text_chunks = chunk_text(&content, &chunker_config);
+            // Summaries are derived from the whole lockfile and map to none of its lines
+            pending.extend(pending_chunks(&file, text_chunks, metadata.len(), last_modified, |_| None));
+            lockfiles += 1;
+        }
+        if self.options.verbose && lockfiles > 0 {
+            println!("Staged dependency summaries of {} lockfiles", lockfiles);
+        }
+        Ok(pending)
+    }
+
     /// Processes a single file by chunking, cleaning, and generating embeddings.
     async fn process_file_stage_chunks(
         file_path: &Path,
         relative_path: &str,
         chunker_config: &ChunkerConfig,
+        cleaning: &CleaningOptions,
+        header_index: Option<&HeaderIndex>,
+        data_summary: Option<&DataSummaryOptions>,
         verbose: bool,
     ) -> Result<Vec<PendingChunk>> {
         // Read file content
-        let content = fs::read_to_string(file_path).await?;
-        let content = clean_and_redact(&content);
+        let original = fs::read_to_string(file_path).await?;
+        let mut content = original.clone();
+        // Spans are only mapped back for content derived line by line from the file
+        let mut mapped = true;
+        let mut notebook = None;
+        if is_notebook(file_path) {
+            // Outputs are mostly noise for retrieval
+            let extracted = extract_notebook_lines(&content, &NotebookOptions::without_outputs())?;
+            content = extracted.text.clone();
+            notebook = Some(extracted);
+        }
+        if let Some(summary) = data_summary.and_then(|options| summarize_data_file(file_path, &content, options)) {
+            content = summary;
+            mapped = false;
+        }
+        let content = match header_index {
+            Some(index) => index.strip(&content).0,
+            None => content,
+        };
+        let (content, _) = clean_and_redact_with_options(&content, relative_path, cleaning);
 
         if content.trim().is_empty() { return Ok(vec![]); }
 
@@ -414,38 +758,74 @@ impl JsonDatabaseGenerator {
         let last_modified = metadata
             .modified()
             .ok()
-            .and_then(|time| {
+            .map(|time| {
                 let datetime: DateTime<Utc> = time.into();
-                Some(datetime.to_rfc3339())
+                datetime.to_rfc3339()
             });
 
         // Chunk the file content
-        let text_chunks = chunk_text(&content, chunker_config);
-        let total_chunks = text_chunks.len();
+        let text_chunks = chunk_file(&content, relative_path, chunker_config);
+        // Notebook chunks map to the extracted cells first, then to the lines of the notebook file
+        let extracted = notebook.as_ref().map_or(original.as_str(), |notebook| notebook.text.as_str());
+        let source_map = mapped.then(|| SourceMap::new(extracted, &content));
+        let file_index = notebook.as_ref().map(|_| LineIndex::new(&original));
+        let map_span = |span: &SourceSpan| {
+            let span = source_map.as_ref()?.span(span);
+            Some(match (&notebook, &file_index) {
+                (Some(notebook), Some(index)) => notebook.file_span(&span, index),
+                _ => span,
+            })
+        };
 
         if text_chunks.is_empty() { return Ok(vec![]); }
 
-        if verbose { println!("  - Staged {} chunks", total_chunks); }
+        if verbose { println!("  - Staged {} chunks", text_chunks.len()); }
 
         // Build pending chunks (no embeddings yet)
-        let pending: Vec<PendingChunk> = text_chunks
-            .into_iter()
-            .map(|text_chunk| PendingChunk {
-                file_path: relative_path.to_string(),
-                content: text_chunk.content,
-                metadata: ChunkMetadata {
-                    chunk_index: text_chunk.chunk_index,
-                    total_chunks,
-                    file_size,
-                    last_modified: last_modified.clone(),
-                    start_index: text_chunk.start_index,
-                    end_index: text_chunk.end_index,
-                },
-            })
-            .collect();
+        Ok(pending_chunks(relative_path, text_chunks, file_size, last_modified, map_span))
+    }
+}
 
-        Ok(pending)
+/// The chunker config for `file_path` with the tokens of its context header taken out of the chunk
+/// size, when `header_budget` leaves less room than the configured size.
+fn reserve_header(config: &ChunkerConfig, file_path: &str, header_budget: Option<usize>) -> ChunkerConfig {
+    let mut config = config.clone();
+    if let Some(budget) = header_budget {
+        let header = embedding_input(Some(&context_header(file_path, None, None)), "");
+        config.chunk_size = config.chunk_size.min(budget.saturating_sub(config.count_tokens(&header)).max(1));
+        config.overlap_size = config.overlap_size.min(config.chunk_size / 2);
     }
+    config
+}
+
+/// Pending chunks of one file's text chunks; `map_span` maps their spans back to the file.
+fn pending_chunks(
+    relative_path: &str,
+    text_chunks: Vec<TextChunk>,
+    file_size: u64,
+    last_modified: Option<String>,
+    map_span: impl Fn(&SourceSpan) -> Option<SourceSpan>,
+) -> Vec<PendingChunk> {
+    let total_chunks = text_chunks.len();
+    text_chunks
+        .into_iter()
+        .map(|text_chunk| PendingChunk {
+            file_path: relative_path.to_string(),
+            content: text_chunk.content,
+            context: None,
+            metadata: ChunkMetadata {
+                chunk_index: text_chunk.chunk_index,
+                total_chunks,
+                file_size,
+                last_modified: last_modified.clone(),
+                start_index: text_chunk.start_index,
+                end_index: text_chunk.end_index,
+                span: map_span(&text_chunk.span),
+                heading_path: text_chunk.heading_path,
+                parent: text_chunk.parent,
+            },
+        })
+        .collect()
 }
 
 // ================= Embedding worker pool (no global mutex) =================
@@ -462,10 +842,11 @@ struct EmbeddingPool(Arc<EmbeddingPoolInner>);
 struct EmbeddingPoolInner {
     senders: Vec<mpsc::Sender<EmbeddingJob>>, // per-worker input queues
     next: AtomicUsize,
+    tokenizer: ModelTokenizer, // shared by all workers' models
 }
 
 impl EmbeddingPool {
-    fn new(pool_size: usize) -> Result<Self> {
+    fn new(backend: &EmbeddingBackendConfig, pool_size: usize) -> Result<Self> {
         let size = pool_size.max(1);
         let mut senders = Vec::with_capacity(size);
         let mut readiness_rxs = Vec::with_capacity(size);
@@ -474,14 +855,15 @@ impl EmbeddingPool {
             // Increase queue capacity to reduce backpressure causing transient send failures.
             let (tx, mut rx) = mpsc::channel::<EmbeddingJob>(32);
             // One-shot readiness signal from worker -> pool (std mpsc so we can recv_timeout)
-            let (ready_tx, ready_rx) = std_mpsc::channel::<Result<()>>();
+            let (ready_tx, ready_rx) = std_mpsc::channel::<Result<ModelTokenizer>>();
+            let backend = backend.clone();
             // Spawn a dedicated OS thread for the worker so heavy compute doesn't block the async runtime.
             std::thread::spawn(move || {
                 // Initialize the model inside the worker thread.
-                let mut generator = match EmbeddingsGenerator::new() {
-                    Ok(g) => {
-                        // Signal readiness to the pool
-                        let _ = ready_tx.send(Ok(()));
+                let mut generator = match backend.create().and_then(|g| Ok((g.tokenizer()?, g))) {
+                    Ok((tokenizer, g)) => {
+                        // Signal readiness to the pool, handing over the model's tokenizer
+                        let _ = ready_tx.send(Ok(tokenizer));
                         g
                     }
                     Err(e) => {
@@ -501,7 +883,7 @@ impl EmbeddingPool {
                     // Catch panics inside the worker so callers receive a proper error instead of a dropped channel.
                     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                         generator
-                            .generate_embeddings(texts_refs, job.batch_size)
+                            .embed(&texts_refs, job.batch_size)
                     }))
                     .map_err(|_| anyhow::anyhow!("embedding worker {} panicked during generate", worker_id))
                     .and_then(|res| res.map_err(|e| anyhow::anyhow!(e)));
@@ -520,9 +902,12 @@ impl EmbeddingPool {
             .and_then(|s| s.parse().ok())
             .unwrap_or(20);
         let start_wait = Instant::now();
+        let mut tokenizer = None;
         for (idx, rx) in readiness_rxs.into_iter().enumerate() {
             match rx.recv_timeout(std::time::Duration::from_secs(init_timeout_secs)) {
-                Ok(Ok(())) => { /* ready */ }
+                Ok(Ok(worker_tokenizer)) => {
+                    tokenizer.get_or_insert(worker_tokenizer);
+                }
                 Ok(Err(e)) => {
                     return Err(anyhow::anyhow!(format!(
                         "embedding pool init failed: worker {} not ready: {}",
@@ -542,44 +927,10 @@ impl EmbeddingPool {
         Ok(Self(Arc::new(EmbeddingPoolInner {
             senders,
             next: AtomicUsize::new(0),
+            tokenizer: tokenizer.ok_or_else(|| anyhow::anyhow!("embedding pool has no workers"))?,
         })))
     }
 
-    async fn embed(&self, texts: Vec<String>, batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
-        let inner = &self.0;
-        let len = inner.senders.len();
-        let idx = inner.next.fetch_add(1, Ordering::Relaxed) % len;
-        let (resp_tx, resp_rx) = oneshot::channel();
-        let job = EmbeddingJob {
-            texts,
-            batch_size,
-            resp: resp_tx,
-        };
-        inner
-            .senders[idx]
-            .send(job)
-            .await
-            .map_err(|e| anyhow::anyhow!(
-                "failed to send embedding job: {}. hint: worker may have failed to initialize; try setting ORT_DISABLE_COREML=1 to force CPU or check startup logs.",
-                e
-            ))?;
-
-        // Optional timeout to avoid hanging forever if a worker wedges.
-        let timeout_secs: u64 = std::env::var("TOAK_EMBED_TIMEOUT_SECS")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(120);
-
-        match tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), resp_rx).await {
-            Ok(Ok(res)) => res,
-            Ok(Err(e)) => Err(anyhow::anyhow!("embedding worker dropped: {}", e)),
-            Err(_) => Err(anyhow::anyhow!(
-                "embedding job timed out after {}s; worker may be stalled",
-                timeout_secs
-            )),
-        }
-    }
-
     /// Embed a large set of texts by slicing into per-job batches and
     /// dispatching them across workers in parallel. Preserves the global order.
     async fn embed_many_ordered(
@@ -632,7 +983,7 @@ impl EmbeddingPool {
             .and_then(|s| s.parse().ok())
             .unwrap_or(120);
 
-        for (start, rx) in starts.into_iter().zip(futures.into_iter()) {
+        for (start, rx) in starts.into_iter().zip(futures) {
             let batch = match tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), rx).await {
                 Ok(Ok(res)) => res?,
                 Ok(Err(e)) => return Err(anyhow::anyhow!("embedding worker dropped: {}", e)),
@@ -655,5 +1006,103 @@ impl EmbeddingPool {
 pub struct JsonDatabaseResult {
     pub success: bool,
     pub total_files: usize,
+    /// Unique chunks, each with its own embedding
     pub total_chunks: usize,
+    /// Places chunk content occurs, counting the duplicates of unique chunks
+    pub total_locations: usize,
+    /// Chunks longer than the embedding model's maximum input length, which it truncated
+    pub truncated_chunks: usize,
+    /// Chunks whose content duplicated another chunk's, stored as its duplicates
+    pub duplicate_chunks: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding_backend::OpenAiConfig;
+
+    fn pending(file_path: &str, content: &str, chunk_index: usize) -> PendingChunk {
+        PendingChunk {
+            file_path: file_path.to_string(),
+            content: content.to_string(),
+            context: None,
+            metadata: ChunkMetadata {
+                chunk_index,
+                total_chunks: 2,
+                file_size: 0,
+                last_modified: None,
+                start_index: 0,
+                end_index: 1,
+                span: None,
+                heading_path: None,
+                parent: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_deduplicates_identical_content() {
+        let unique = deduplicate(vec![
+            pending("src/a.rs", "fn shared() {}", 0),
+            pending("src/a.rs", "fn a() {}", 1),
+            pending("vendor/a.rs", "fn shared() {}", 0),
+            pending("gen/a.rs", "fn shared() {}", 1),
+        ]);
+
+        assert_eq!(unique.len(), 2);
+        assert_eq!(unique[0].chunk.file_path, "src/a.rs");
+        assert_eq!(unique[0].content_hash, content_hash("fn shared() {}"));
+        let duplicates: Vec<_> = unique[0].duplicates.iter().map(|location| (location.file_path.as_str(), location.metadata.chunk_index)).collect();
+        assert_eq!(duplicates, [("vendor/a.rs", 0), ("gen/a.rs", 1)]);
+        assert!(unique[1].duplicates.is_empty());
+    }
+
+    #[test]
+    fn test_shared_chunks_have_no_context_header() {
+        let mut unique = deduplicate(vec![
+            pending("src/a.rs", "fn shared() {}", 0),
+            pending("vendor/a.rs", "fn shared() {}", 0),
+            pending("src/b.rs", "fn overlap() {}", 0),
+            pending("src/b.rs", "fn overlap() {}", 1),
+        ]);
+        add_context_headers(&mut unique, &ModelTokenizer::estimated(512));
+        assert_eq!(unique[0].chunk.context, None);
+        assert_eq!(unique[1].chunk.context.as_deref(), Some("File: src/b.rs\nLanguage: Rust"));
+    }
+
+    #[test]
+    fn test_chunk_size_is_lowered_to_fit_the_model() {
+        let mut endpoint = OpenAiConfig::new("http://localhost:1", "small-model");
+        endpoint.max_input_length = 256;
+        let dir = std::env::temp_dir().join(format!("toak-chunk-size-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let generator = JsonDatabaseGenerator::new(JsonDatabaseOptions {
+            dir: dir.clone(),
+            embedding_backend: EmbeddingBackendConfig::OpenAi(endpoint),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(generator.options.chunker_config.chunk_size, 256);
+        assert!(generator.options.chunker_config.overlap_size <= 128);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_context_header_tokens_are_reserved() {
+        let config = ChunkerConfig { chunk_size: 100, overlap_size: 80, ..Default::default() };
+        let header = embedding_input(Some(&context_header("src/widget.rs", None, None)), "");
+        let reserved = reserve_header(&config, "src/widget.rs", Some(100));
+        assert_eq!(reserved.chunk_size, 100 - config.count_tokens(&header));
+        assert_eq!(reserved.overlap_size, reserved.chunk_size / 2);
+        // A budget with room for both leaves the chunk size alone
+        assert_eq!(reserve_header(&config, "src/widget.rs", Some(1000)).chunk_size, 100);
+        assert_eq!(reserve_header(&config, "src/widget.rs", None).chunk_size, 100);
+    }
+
+    #[test]
+    fn test_vector_shape() {
+        assert_eq!(vector_shape(&[vec![0.6, 0.8], vec![1.0, 0.0]]).unwrap(), (2, true));
+        assert_eq!(vector_shape(&[vec![0.6, 0.8], vec![3.0, 4.0]]).unwrap(), (2, false));
+        assert!(vector_shape(&[vec![1.0, 0.0], vec![1.0]]).is_err());
+    }
 }
@@ -0,0 +1,505 @@
+//! Language-aware removal of imports and debug logging. Import blocks can span several lines
+//! (Rust `use` trees, Go `import ( … )`, parenthesized Python imports), so each language gets
+//! its own patterns instead of the line-based JavaScript ones in the generic cleaner.
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Languages with dedicated import and debug-log stripping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeLanguage {
+  Rust,
+  Python,
+  Go,
+  Java,
+  Kotlin,
+  C,
+  JavaScript,
+}
+
+impl CodeLanguage {
+  pub const ALL: [CodeLanguage; 7] = [
+    CodeLanguage::Rust,
+    CodeLanguage::Python,
+    CodeLanguage::Go,
+    CodeLanguage::Java,
+    CodeLanguage::Kotlin,
+    CodeLanguage::C,
+    CodeLanguage::JavaScript,
+  ];
+
+  /// Language of `file_path` judged by its extension, if it has dedicated rules.
+  pub fn from_path(file_path: &str) -> Option<Self> {
+    let ext = Path::new(file_path)
+      .extension()
+      .and_then(|e| e.to_str())
+      .map(|e| e.to_ascii_lowercase())?;
+    match ext.as_str() {
+      "rs" => Some(Self::Rust),
+      "py" | "pyi" => Some(Self::Python),
+      "go" => Some(Self::Go),
+      "java" => Some(Self::Java),
+      "kt" | "kts" => Some(Self::Kotlin),
+      "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" | "m" | "mm" => Some(Self::C),
+      "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Some(Self::JavaScript),
+      _ => None,
+    }
+  }
+
+  /// Human-readable name of the language. C and JavaScript cover their supersets, whose imports
+  /// and logging share the same syntax.
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Rust => "Rust",
+      Self::Python => "Python",
+      Self::Go => "Go",
+      Self::Java => "Java",
+      Self::Kotlin => "Kotlin",
+      Self::C => "C/C++",
+      Self::JavaScript => "JavaScript/TypeScript",
+    }
+  }
+
+  /// Characters that open string literals, used to skip parentheses inside strings.
+  fn quote_chars(self) -> &'static [char] {
+    match self {
+      // Single quotes are lifetimes or char literals in Rust, runes in Go
+      Self::Rust | Self::Go => &['"'],
+      Self::JavaScript => &['"', '\'', '`'],
+      Self::Python | Self::Java | Self::Kotlin | Self::C => &['"', '\''],
+    }
+  }
+}
+
+impl std::str::FromStr for CodeLanguage {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "rust" | "rs" => Ok(Self::Rust),
+      "python" | "py" => Ok(Self::Python),
+      "go" => Ok(Self::Go),
+      "java" => Ok(Self::Java),
+      "kotlin" | "kt" => Ok(Self::Kotlin),
+      "c" | "cpp" | "c++" => Ok(Self::C),
+      "javascript" | "js" | "typescript" | "ts" => Ok(Self::JavaScript),
+      other => Err(format!(
+        "unknown language '{}' (expected rust, python, go, java, kotlin, c or javascript)",
+        other
+      )),
+    }
+  }
+}
+
+/// Which languages get import and debug-log stripping. All are enabled by default.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageStripping {
+  /// Languages whose imports and logging are kept as written
+  pub disabled: HashSet<CodeLanguage>,
+}
+
+impl LanguageStripping {
+  pub fn enabled(&self, language: CodeLanguage) -> bool {
+    !self.disabled.contains(&language)
+  }
+}
+
+/// Import patterns and debug-log call prefixes for one language.
+struct LanguageRules {
+  imports: Vec<Regex>,
+  /// Matches the start of a logging statement up to (not including) its opening parenthesis
+  log_calls: Option<Regex>,
+}
+
+static LANGUAGE_RULES: OnceLock<Vec<(CodeLanguage, LanguageRules)>> = OnceLock::new();
+
+fn get_language_rules(language: CodeLanguage) -> &'static LanguageRules {
+  let rules = LANGUAGE_RULES.get_or_init(|| {
+    let compile = |patterns: &[&str]| -> Vec<Regex> {
+      patterns
+        .iter()
+        .map(|p| Regex::new(&format!("(?m){}", p)).expect("valid import pattern"))
+        .collect()
+    };
+    let calls = |prefix: &str| Some(Regex::new(&format!(r"(?m)^[ \t]*(?:{})[ \t]*\(", prefix)).expect("valid log pattern"));
+    let jvm_logs = r"System\.(?:out|err)\.print(?:ln|f)?|(?:log|logger|LOG|LOGGER)\.(?:debug|trace)|Log\.[dv]";
+
+    vec![
+      (
+        CodeLanguage::Rust,
+        LanguageRules {
+          // `[^;]*` spans the lines of a nested use tree
+          imports: compile(&[r"^[ \t]*(?:pub(?:\([^)]*\))?[ \t]+)?(?:use|extern[ \t]+crate)[ \t][^;]*;[ \t]*(?:\n|$)"]),
+          log_calls: calls(r"(?:std::)?(?:e?println|e?print|dbg)!|(?:log::)?(?:debug|trace)!"),
+        },
+      ),
+      (
+        CodeLanguage::Python,
+        LanguageRules {
+          imports: compile(&[
+            r"^[ \t]*from[ \t]+\S+[ \t]+import[ \t]*\([^)]*\)[ \t]*(?:\n|$)",
+            r"^[ \t]*(?:from[ \t]+\S+[ \t]+)?import[ \t]+(?:[^\n\\]|\\\n)*(?:\n|$)",
+          ]),
+          log_calls: calls(r"print|(?:self\.)?(?:logging|logger|log)\.debug"),
+        },
+      ),
+      (
+        CodeLanguage::Go,
+        LanguageRules {
+          imports: compile(&[
+            r"^[ \t]*import[ \t]*\([^)]*\)[ \t]*(?:\n|$)",
+            r#"^[ \t]*import[ \t]+(?:[\w.]+[ \t]+)?"[^"\n]*"[ \t]*(?:\n|$)"#,
+          ]),
+          log_calls: calls(r"fmt\.Print(?:ln|f)?"),
+        },
+      ),
+      (
+        CodeLanguage::Java,
+        LanguageRules {
+          imports: compile(&[r"^[ \t]*import[ \t]+(?:static[ \t]+)?[\w.*]+[ \t]*;[ \t]*(?:\n|$)"]),
+          log_calls: calls(jvm_logs),
+        },
+      ),
+      (
+        CodeLanguage::Kotlin,
+        LanguageRules {
+          imports: compile(&[r"^[ \t]*import[ \t]+[\w.*`]+(?:[ \t]+as[ \t]+\w+)?[ \t]*;?[ \t]*(?:\n|$)"]),
+          log_calls: calls(&format!("{}|println|print", jvm_logs)),
+        },
+      ),
+      (
+        CodeLanguage::C,
+        LanguageRules {
+          // printf is usually real output in C, so only includes are stripped
+          imports: compile(&[r#"^[ \t]*#[ \t]*include[ \t]*[<"][^>"\n]*[>"][^\n]*(?:\n|$)"#]),
+          log_calls: None,
+        },
+      ),
+      (
+        CodeLanguage::JavaScript,
+        LanguageRules {
+          imports: compile(&[
+            r#"^[ \t]*import[ \t]+(?:type[ \t]+)?[^;'"]*?from[ \t]*['"][^'"\n]*['"][ \t]*;?[ \t]*(?:\n|$)"#,
+            r#"^[ \t]*import[ \t]*['"][^'"\n]*['"][ \t]*;?[ \t]*(?:\n|$)"#,
+          ]),
+          log_calls: calls(r"console\.(?:log|debug|info|warn|error|trace)"),
+        },
+      ),
+    ]
+  });
+
+  rules
+    .iter()
+    .find(|(lang, _)| *lang == language)
+    .map(|(_, rules)| rules)
+    .expect("rules for every language")
+}
+
+/// Byte offset just past the parenthesis closing the one at `open`, skipping string literals.
+fn matching_paren(text: &str, open: usize, quotes: &[char]) -> Option<usize> {
+  let mut depth = 0usize;
+  let mut in_string: Option<char> = None;
+  let mut escaped = false;
+
+  for (offset, c) in text[open..].char_indices() {
+    if let Some(quote) = in_string {
+      if escaped {
+        escaped = false;
+      } else if c == '\\' {
+        escaped = true;
+      } else if c == quote {
+        in_string = None;
+      }
+      continue;
+    }
+    match c {
+      c if quotes.contains(&c) => in_string = Some(c),
+      '(' => depth += 1,
+      ')' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(open + offset + 1);
+        }
+      }
+      _ => {}
+    }
+  }
+  None
+}
+
+/// Removes logging calls that make up a whole statement. Calls whose value is used (chained,
+/// assigned, passed on) are left alone.
+fn remove_log_statements(code: &str, calls: &Regex, quotes: &[char]) -> String {
+  let mut output = String::with_capacity(code.len());
+  let mut cursor = 0;
+
+  for m in calls.find_iter(code) {
+    if m.start() < cursor {
+      continue;
+    }
+    let Some(close) = matching_paren(code, m.end() - 1, quotes) else {
+      continue;
+    };
+    let rest_of_line = code[close..].split('\n').next().unwrap_or_default();
+    let trailing = rest_of_line.trim();
+    if !(trailing.is_empty() || trailing == ";") {
+      continue;
+    }
+    let end = (close + rest_of_line.len() + 1).min(code.len());
+    output.push_str(&code[cursor..m.start()]);
+    cursor = end;
+  }
+
+  output.push_str(&code[cursor..]);
+  output
+}
+
+/// Strips imports and debug logging from `code` written in `language`.
+pub fn strip_imports_and_logs(code: &str, language: CodeLanguage) -> String {
+  let rules = get_language_rules(language);
+  let mut result = code.to_string();
+  for re in &rules.imports {
+    result = re.replace_all(&result, "").to_string();
+  }
+  match &rules.log_calls {
+    Some(calls) => remove_log_statements(&result, calls, language.quote_chars()),
+    None => result,
+  }
+}
+
+/// Opening triple quote of a string statement on `trimmed`, with any `r`/`u` prefix.
+fn docstring_delimiter(trimmed: &str) -> Option<&'static str> {
+  let body = trimmed.trim_start_matches(['r', 'R', 'u', 'U']);
+  if body.len() + 1 < trimmed.len() {
+    return None;
+  }
+  ["\"\"\"", "'''"].into_iter().find(|d| body.starts_with(d))
+}
+
+/// Whether `trimmed` starts a `def` or `class` header, whose body may open with a docstring.
+fn starts_definition(trimmed: &str) -> bool {
+  let trimmed = trimmed
+    .strip_prefix("async ")
+    .unwrap_or(trimmed)
+    .trim_start();
+  ["def ", "class "]
+    .iter()
+    .any(|keyword| trimmed.starts_with(keyword))
+}
+
+/// Removes full-line `#` comments from Python source (keeping a leading shebang) and, unless
+/// `keep_docstrings` is set, docstrings: strings that are the first statement of the module or
+/// of a `def` or `class` body. Other strings, such as a parenthesised SQL literal, are code.
+pub(crate) fn strip_python_comments(code: &str, keep_docstrings: bool) -> String {
+  let mut kept: Vec<&str> = Vec::new();
+  // Delimiter of the multi-line string being read, and whether its lines are kept
+  let mut open_string: Option<(&str, bool)> = None;
+  // Whether the next statement is the first of the module or of a definition's body
+  let mut body_start = true;
+  // Whether a `def` or `class` header has started but not yet reached its colon
+  let mut in_header = false;
+
+  for (idx, line) in code.lines().enumerate() {
+    if let Some((delimiter, keep)) = open_string {
+      if keep {
+        kept.push(line);
+      }
+      if line.contains(delimiter) {
+        open_string = None;
+      }
+      continue;
+    }
+
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') {
+      if idx == 0 && trimmed.starts_with("#!") {
+        kept.push(line);
+      }
+      continue;
+    }
+
+    if trimmed.is_empty() {
+      kept.push(line);
+      continue;
+    }
+
+    let docstring = docstring_delimiter(trimmed).filter(|_| body_start);
+    in_header |= starts_definition(trimmed);
+    let code_part = trimmed.split(" #").next().unwrap_or(trimmed).trim_end();
+    body_start = in_header && code_part.ends_with(':');
+    if body_start {
+      in_header = false;
+    }
+
+    if let Some(delimiter) = docstring {
+      let after_open = &trimmed[trimmed.find(delimiter).unwrap_or(0) + 3..];
+      if !after_open.contains(delimiter) {
+        open_string = Some((delimiter, keep_docstrings));
+      }
+      if keep_docstrings {
+        kept.push(line);
+      }
+      continue;
+    }
+
+    // Comment-looking lines inside other multi-line strings are data, not comments
+    for delimiter in ["\"\"\"", "'''"] {
+      if line.matches(delimiter).count() % 2 == 1 {
+        open_string = Some((delimiter, true));
+        break;
+      }
+    }
+    kept.push(line);
+  }
+
+  kept.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rust_use_trees_and_macros() {
+    let code = "\
+use std::io;
+pub(crate) use crate::{
+    a::B,
+    c::D,
+};
+fn main() {
+    println!(\"value: {}\", f(1));
+    dbg!(&x);
+    let y = dbg!(x) + 1;
+    eprintln!(\"a ) in a string\");
+}";
+    assert_eq!(
+      strip_imports_and_logs(code, CodeLanguage::Rust),
+      "fn main() {\n    let y = dbg!(x) + 1;\n}"
+    );
+  }
+
+  #[test]
+  fn test_python_and_go_import_blocks() {
+    let python = "\
+import os, \\
+    sys
+from typing import (
+    Any,
+    Dict,
+)
+from x import y
+def f():
+    print('debug', os.getcwd())
+    logger.debug(\"state %s\", s)
+    return y";
+    assert_eq!(
+      strip_imports_and_logs(python, CodeLanguage::Python),
+      "def f():\n    return y"
+    );
+
+    let go = "package main\n\nimport (\n\t\"fmt\"\n\tlog \"github.com/x/log\"\n)\nimport \"os\"\n\nfunc main() {\n\tfmt.Println(\"hi\")\n\tos.Exit(1)\n}";
+    assert_eq!(
+      strip_imports_and_logs(go, CodeLanguage::Go),
+      "package main\n\n\nfunc main() {\n\tos.Exit(1)\n}"
+    );
+  }
+
+  #[test]
+  fn test_jvm_c_and_javascript() {
+    let java = "import java.util.List;\nimport static org.junit.Assert.*;\nclass A {\n  void f() {\n    System.out.println(\"x\");\n    log.debug(\"y\");\n  }\n}";
+    assert_eq!(
+      strip_imports_and_logs(java, CodeLanguage::Java),
+      "class A {\n  void f() {\n  }\n}"
+    );
+
+    let kotlin =
+      "import kotlinx.coroutines.launch\nimport a.b.C as D\nfun main() {\n    println(\"hi\")\n}";
+    assert_eq!(
+      strip_imports_and_logs(kotlin, CodeLanguage::Kotlin),
+      "fun main() {\n}"
+    );
+
+    let c = "#include <stdio.h>\n# include \"local.h\"\nint main() { printf(\"%d\", 1); }";
+    assert_eq!(
+      strip_imports_and_logs(c, CodeLanguage::C),
+      "int main() { printf(\"%d\", 1); }"
+    );
+
+    let js = "import {\n  a,\n  b,\n} from './x';\nimport './side-effect.css';\nconsole.log(`(`, a);\nexport const c = a + b;";
+    assert_eq!(
+      strip_imports_and_logs(js, CodeLanguage::JavaScript),
+      "export const c = a + b;"
+    );
+  }
+
+  #[test]
+  fn test_language_detection_and_toggles() {
+    assert_eq!(
+      CodeLanguage::from_path("src/lib.rs"),
+      Some(CodeLanguage::Rust)
+    );
+    assert_eq!(
+      CodeLanguage::from_path("web/App.TSX"),
+      Some(CodeLanguage::JavaScript)
+    );
+    assert_eq!(CodeLanguage::from_path("README.md"), None);
+
+    let stripping = LanguageStripping {
+      disabled: ["python".parse().unwrap()].into_iter().collect(),
+    };
+    assert!(!stripping.enabled(CodeLanguage::Python));
+    assert!(stripping.enabled(CodeLanguage::Go));
+  }
+
+  #[test]
+  fn test_python_comments_and_docstrings() {
+    let code = "#!/usr/bin/env python\n# module note\n\"\"\"Module docs.\"\"\"\nQUERY = \"\"\"\n# not a comment\n\"\"\"\ndef f():\n    r\'\'\'Docs.\n    More.\n    \'\'\'\n    return 1  # trailing";
+    assert_eq!(
+      strip_python_comments(code, true),
+      "#!/usr/bin/env python\n\"\"\"Module docs.\"\"\"\nQUERY = \"\"\"\n# not a comment\n\"\"\"\ndef f():\n    r\'\'\'Docs.\n    More.\n    \'\'\'\n    return 1  # trailing"
+    );
+    assert_eq!(
+      strip_python_comments(code, false),
+      "#!/usr/bin/env python\nQUERY = \"\"\"\n# not a comment\n\"\"\"\ndef f():\n    return 1  # trailing"
+    );
+  }
+
+  #[test]
+  fn test_python_strings_outside_docstring_position_are_kept() {
+    let code = "\
+import db
+QUERY = (
+    \"\"\"SELECT id
+    FROM users\"\"\"
+)
+class Repo(
+    Base,
+):
+    '''Docs.'''
+    TABLE = (
+        '''users'''
+    )
+    async def get(self):  # fetch
+        \"\"\"Docs.\"\"\"
+        return db.run(QUERY)";
+    assert_eq!(
+      strip_python_comments(code, false),
+      "\
+import db
+QUERY = (
+    \"\"\"SELECT id
+    FROM users\"\"\"
+)
+class Repo(
+    Base,
+):
+    TABLE = (
+        '''users'''
+    )
+    async def get(self):  # fetch
+        return db.run(QUERY)"
+    );
+  }
+}
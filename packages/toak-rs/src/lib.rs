@@ -36,20 +36,79 @@
 //! }
 //! ```
 
+pub mod chunk_context;
+pub mod data_summarizer;
+pub mod embedding_backend;
 pub mod embeddings_generator;
+pub mod hashing_embedder;
 pub mod json_database_generator;
+pub mod language_cleaner;
+pub mod license_headers;
+pub mod lockfiles;
 pub mod markdown_generator;
+pub mod model_store;
+pub mod notebook;
+pub mod pii_redactor;
+pub mod prompt_injection;
+pub mod prose_chunker;
+pub mod secret_scanner;
 pub mod semantic_search;
+pub mod source_map;
+#[cfg(feature = "syntax")]
+pub mod syntax_chunker;
 pub mod text_chunker;
 pub mod token_cleaner;
+pub mod unicode_hygiene;
 
 // Re-export commonly used types at the root level
-pub use embeddings_generator::EmbeddingsGenerator;
-pub use json_database_generator::{ChunkMetadata, EmbeddedChunk, EmbeddingsDatabase, JsonDatabaseGenerator, JsonDatabaseOptions, JsonDatabaseResult};
+pub use chunk_context::{context_header, language_name};
+pub use data_summarizer::{summarize_data_file, DataFormat, DataSummaryOptions};
+pub use embedding_backend::{
+    EmbeddingBackend, EmbeddingBackendConfig, OpenAiBackend, OpenAiConfig,
+};
+pub use embeddings_generator::{
+    find_model, is_same_model, max_input_length, model_id, EmbeddingsGenerator, ModelTokenizer,
+    DEFAULT_MODEL,
+};
+pub use fastembed::EmbeddingModel;
+pub use hashing_embedder::{
+    hashing_model_id, HashingEmbedder, DEFAULT_HASHING_DIMENSION, HASHING_MODEL_PREFIX,
+    MAX_HASHING_DIMENSION,
+};
+pub use json_database_generator::{
+    ChunkLocation, ChunkMetadata, EmbeddedChunk, EmbeddingsDatabase, JsonDatabaseGenerator,
+    JsonDatabaseOptions, JsonDatabaseResult,
+};
+pub use language_cleaner::{strip_imports_and_logs, CodeLanguage, LanguageStripping};
+pub use license_headers::{detect_license, HeaderIndex, HeaderSummary, StrippedHeader};
+pub use lockfiles::{dependencies_markdown, Dependency, DependencySummary, LockfileKind};
 pub use markdown_generator::{MarkdownGenerator, MarkdownGeneratorOptions, MarkdownResult};
-pub use semantic_search::{EmbeddingChunk, EmbeddingsDatabaseMetadata, SearchResult, SemanticSearch};
-pub use text_chunker::{chunk_text, ChunkerConfig, TextChunk};
-pub use token_cleaner::{clean_and_redact, clean_code, count_tokens, redact_secrets};
+pub use model_store::{export_model, fetch_model, model_cache_dir, ModelManifest};
+pub use notebook::{extract_notebook, is_notebook, NotebookOptions};
+pub use pii_redactor::{detect_pii, PiiKind, PiiMatch, PiiOptions};
+pub use prompt_injection::{
+    apply_injection_policy, detect_injections, InjectionOptions, InjectionPolicy, InjectionRule,
+    InjectionRules,
+};
+pub use prose_chunker::ProseFormat;
+pub use secret_scanner::{ScanFormat, ScanOptions, ScanResult, SecretScanner};
+pub use semantic_search::{
+    EmbeddingChunk, EmbeddingsDatabaseMetadata, SearchResult, SemanticSearch,
+};
+pub use source_map::{SourceMap, SourcePosition, SourceSpan};
+#[cfg(feature = "syntax")]
+pub use syntax_chunker::supports_syntax_chunking;
+pub use text_chunker::{
+    chunk_file, chunk_text, Chunker, ChunkerConfig, ChunkingStrategy, TextChunk, TokenCounter,
+};
+pub use token_cleaner::{
+    clean_and_redact, clean_and_redact_with_findings, clean_and_redact_with_options, clean_code,
+    clean_code_for, count_tokens, redact_secrets, redact_secrets_with_findings,
+    redact_secrets_with_options, scan_secrets, secret_rule_description, CleaningOptions,
+    CommentMode, FingerprintKey, Pseudonymizer, RedactedLinePolicy, RedactionFinding,
+    RedactionMode, RedactionOptions, RedactionReport, SecretAllowlist, Severity, StrippingOptions,
+};
+pub use unicode_hygiene::{sanitize_unicode, UnicodePolicy};
 
 /// Prelude module for convenient imports
 ///
@@ -59,9 +118,17 @@ pub use token_cleaner::{clean_and_redact, clean_code, count_tokens, redact_secre
 /// ```
 pub mod prelude {
     pub use crate::{
-        chunk_text, clean_and_redact, clean_code, count_tokens, redact_secrets, ChunkMetadata, ChunkerConfig,
-        EmbeddedChunk, EmbeddingChunk, EmbeddingsDatabase, EmbeddingsDatabaseMetadata, EmbeddingsGenerator,
-        JsonDatabaseGenerator, JsonDatabaseOptions, JsonDatabaseResult, MarkdownGenerator,
-        MarkdownGeneratorOptions, MarkdownResult, SearchResult, SemanticSearch, TextChunk,
+        chunk_file, chunk_text, clean_and_redact, clean_and_redact_with_findings,
+        clean_and_redact_with_options, clean_code, clean_code_for, count_tokens, redact_secrets,
+        redact_secrets_with_findings, redact_secrets_with_options, scan_secrets, ChunkLocation,
+        ChunkMetadata, Chunker, ChunkerConfig, ChunkingStrategy, CleaningOptions, CodeLanguage,
+        CommentMode, DataSummaryOptions, DependencySummary, EmbeddedChunk, EmbeddingBackend,
+        EmbeddingBackendConfig, EmbeddingChunk, EmbeddingsDatabase, EmbeddingsDatabaseMetadata,
+        EmbeddingsGenerator, HashingEmbedder, InjectionOptions, InjectionPolicy, InjectionRules,
+        JsonDatabaseGenerator, JsonDatabaseOptions, JsonDatabaseResult, LanguageStripping,
+        MarkdownGenerator, MarkdownGeneratorOptions, MarkdownResult, PiiKind, PiiOptions,
+        Pseudonymizer, RedactedLinePolicy, RedactionFinding, RedactionMode, RedactionOptions,
+        RedactionReport, ScanFormat, ScanOptions, ScanResult, SearchResult, SecretAllowlist,
+        SecretScanner, SemanticSearch, Severity, StrippingOptions, TextChunk, UnicodePolicy,
     };
 }
\ No newline at end of file
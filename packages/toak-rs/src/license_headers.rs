@@ -0,0 +1,594 @@
+//! Removal of license headers and boilerplate banners. A leading comment block is boilerplate
+//! when it holds nothing but a known license notice (its text, copyright lines and SPDX tags),
+//! or when the same block (compared line by line after normalizing comment markers, whitespace
+//! and years) starts many files. Removed headers are summarized once instead of being repeated
+//! in every file.
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::OnceLock;
+use tokio::fs;
+
+/// Only the first lines of a file are searched for a header
+const MAX_HEADER_LINES: usize = 80;
+/// Shared prefixes shorter than this are too generic to treat as a banner
+const MIN_PREFIX_LINES: usize = 3;
+
+/// Known license texts as (SPDX id, phrase that identifies it), checked in order.
+const LICENSE_PHRASES: &[(&str, &str)] = &[
+  ("AGPL-3.0", "gnu affero general public license"),
+  ("LGPL-3.0", "gnu lesser general public license"),
+  ("GPL-3.0", "gnu general public license"),
+  ("Apache-2.0", "apache license, version 2.0"),
+  ("MPL-2.0", "mozilla public license, v. 2.0"),
+  ("MIT", "permission is hereby granted, free of charge"),
+  ("BSD-3-Clause", "neither the name of"),
+  (
+    "BSD-2-Clause",
+    "redistribution and use in source and binary forms",
+  ),
+  ("EPL-2.0", "eclipse public license"),
+  (
+    "Unlicense",
+    "free and unencumbered software released into the public domain",
+  ),
+];
+
+/// Standard notices license headers are made of: full texts of the short permissive licenses
+/// and the per-file notices of the others. `{gpl}` stands for each GNU license name.
+const LICENSE_NOTICES: &[&str] = &[
+  "Licensed under the Apache License, Version 2.0 (the \"License\"); you may not use this file except in \
+   compliance with the License. You may obtain a copy of the License at \
+   http://www.apache.org/licenses/LICENSE-2.0 Unless required by applicable law or agreed to in writing, \
+   software distributed under the License is distributed on an \"AS IS\" BASIS, WITHOUT WARRANTIES OR \
+   CONDITIONS OF ANY KIND, either express or implied. See the License for the specific language governing \
+   permissions and limitations under the License.",
+  "Permission is hereby granted, free of charge, to any person obtaining a copy of this software and \
+   associated documentation files (the \"Software\"), to deal in the Software without restriction, \
+   including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, \
+   and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, \
+   subject to the following conditions: The above copyright notice and this permission notice shall be \
+   included in all copies or substantial portions of the Software. THE SOFTWARE IS PROVIDED \"AS IS\", \
+   WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF \
+   MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR \
+   COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, \
+   TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS \
+   IN THE SOFTWARE.",
+  "Redistribution and use in source and binary forms, with or without modification, are permitted \
+   provided that the following conditions are met: 1. Redistributions of source code must retain the \
+   above copyright notice, this list of conditions and the following disclaimer. 2. Redistributions in \
+   binary form must reproduce the above copyright notice, this list of conditions and the following \
+   disclaimer in the documentation and/or other materials provided with the distribution. 3. Neither the \
+   name of the copyright holder nor the names of its contributors may be used to endorse or promote \
+   products derived from this software without specific prior written permission. THIS SOFTWARE IS \
+   PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS \"AS IS\" AND ANY EXPRESS OR IMPLIED WARRANTIES, \
+   INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR \
+   PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY \
+   DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED \
+   TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS \
+   INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR \
+   TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF \
+   ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.",
+  "This program is free software: you can redistribute it and/or modify it under the terms of the {gpl} \
+   as published by the Free Software Foundation, either version 3 of the License, or (at your option) any \
+   later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY \
+   WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See \
+   the {gpl} for more details. You should have received a copy of the {gpl} along with this program. If \
+   not, see <http://www.gnu.org/licenses/>.",
+  "This program is free software; you can redistribute it and/or modify it under the terms of the {gpl} \
+   as published by the Free Software Foundation; either version 2 of the License, or (at your option) any \
+   later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY \
+   WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See \
+   the {gpl} for more details. You should have received a copy of the {gpl} along with this program; if \
+   not, write to the Free Software Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA \
+   02110-1301 USA.",
+  "This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0. If a copy of the \
+   MPL was not distributed with this file, You can obtain one at http://mozilla.org/MPL/2.0/.",
+];
+
+const GNU_LICENSES: [&str; 3] = [
+  "GNU General Public License",
+  "GNU Lesser General Public License",
+  "GNU Affero General Public License",
+];
+
+static NORMALIZED_NOTICES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Whether a normalized header line belongs to a license notice: part of a standard notice, a
+/// copyright line or an SPDX tag.
+fn is_license_line(normalized: &str) -> bool {
+  if normalized.is_empty()
+    || ["copyright", "(c)", "©", "spdx-"]
+      .iter()
+      .any(|prefix| normalized.starts_with(prefix))
+    || normalized.contains("all rights reserved")
+  {
+    return true;
+  }
+
+  let notices = NORMALIZED_NOTICES.get_or_init(|| {
+    LICENSE_NOTICES
+      .iter()
+      .flat_map(|notice| {
+        GNU_LICENSES
+          .iter()
+          .map(move |gpl| notice.replace("{gpl}", gpl))
+      })
+      .map(|notice| normalize(&notice).replace("https://", "http://"))
+      .collect()
+  });
+  let line = normalized.replace("https://", "http://");
+  notices.iter().any(|notice| notice.contains(&line))
+}
+
+static SPDX_IDENTIFIER: OnceLock<Regex> = OnceLock::new();
+
+/// SPDX identifier of the license text contained in `text`, if any.
+pub fn detect_license(text: &str) -> Option<String> {
+  let spdx = SPDX_IDENTIFIER.get_or_init(|| {
+    Regex::new(r"SPDX-License-Identifier:\s*([\w.+-]+(?:\s+(?:OR|AND|WITH)\s+[\w.+-]+)*)")
+      .expect("valid SPDX pattern")
+  });
+  if let Some(caps) = spdx.captures(text) {
+    return Some(caps[1].to_string());
+  }
+
+  let lower = text
+    .to_lowercase()
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ");
+  let version_2 = lower.contains("version 2");
+  LICENSE_PHRASES
+    .iter()
+    .find(|(_, phrase)| lower.contains(phrase))
+    .map(|(id, _)| match *id {
+      "LGPL-3.0" if version_2 => "LGPL-2.1".to_string(),
+      "GPL-3.0" if version_2 => "GPL-2.0".to_string(),
+      id => id.to_string(),
+    })
+}
+
+/// A comment line of the leading header block.
+struct HeaderLine {
+  /// Index of the line in the file
+  index: usize,
+  /// Line text without comment markers, lowercased, digits collapsed; empty for separators
+  normalized: String,
+  /// Index of the line closing the block comment this line is part of (itself for line comments)
+  block_end: usize,
+}
+
+fn is_line_comment(trimmed: &str) -> bool {
+  if trimmed.starts_with("///") || trimmed.starts_with("//!") {
+    // Documentation, not boilerplate
+    return false;
+  }
+  if trimmed.starts_with("//") {
+    return true;
+  }
+  // `#` comments, but not `#!`, `#[attr]`, `#include` and other directives
+  if let Some(rest) = trimmed.strip_prefix('#') {
+    return rest.is_empty()
+      || rest.starts_with(|c: char| c.is_whitespace() || matches!(c, '#' | '-' | '=' | '*'));
+  }
+  if let Some(rest) = trimmed.strip_prefix("--") {
+    return rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == '-');
+  }
+  false
+}
+
+fn normalize(line: &str) -> String {
+  let text = line
+    .trim()
+    .trim_start_matches(['/', '*', '#', '-', '!', '<', ';'])
+    .trim_end_matches("-->")
+    .trim_end_matches("*/")
+    .trim()
+    .to_lowercase();
+
+  // Years and versions vary between otherwise identical headers
+  let mut normalized = String::with_capacity(text.len());
+  let mut previous_digit = false;
+  for c in text
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .chars()
+  {
+    if c.is_ascii_digit() {
+      if !previous_digit {
+        normalized.push('0');
+      }
+      previous_digit = true;
+    } else {
+      normalized.push(c);
+      previous_digit = false;
+    }
+  }
+  normalized
+}
+
+/// Lines of the comment block at the start of `content`, after an optional shebang.
+fn header_lines(content: &str) -> Vec<HeaderLine> {
+  let lines: Vec<&str> = content.lines().take(MAX_HEADER_LINES).collect();
+  let mut header = Vec::new();
+  let mut i = 0;
+
+  while i < lines.len() {
+    let trimmed = lines[i].trim();
+    if trimmed.is_empty() || (i == 0 && trimmed.starts_with("#!")) {
+      i += 1;
+      continue;
+    }
+
+    let block_close = if trimmed.starts_with("/*") && !trimmed.starts_with("/*!") {
+      Some("*/")
+    } else if trimmed.starts_with("<!--") {
+      Some("-->")
+    } else {
+      None
+    };
+
+    if let Some(close) = block_close {
+      let Some(end) = (i..lines.len()).find(|&j| {
+        let line = if j == i {
+          &lines[j].trim()[2..]
+        } else {
+          lines[j]
+        };
+        line.contains(close)
+      }) else {
+        // Unterminated within the searched lines: not a header we can safely remove
+        break;
+      };
+      header.extend((i..=end).map(|j| HeaderLine {
+        index: j,
+        normalized: normalize(lines[j]),
+        block_end: end,
+      }));
+      i = end + 1;
+    } else if is_line_comment(trimmed) {
+      header.push(HeaderLine {
+        index: i,
+        normalized: normalize(lines[i]),
+        block_end: i,
+      });
+      i += 1;
+    } else {
+      break;
+    }
+  }
+
+  header
+}
+
+/// Position in `header` of the last line of its leading license notice, if it starts with one.
+/// The notice must name a known license and end with a whole comment.
+fn license_notice_end(header: &[HeaderLine], lines: &[&str]) -> Option<usize> {
+  let notice_lines = header
+    .iter()
+    .take_while(|line| is_license_line(&line.normalized))
+    .count();
+  let last = (0..notice_lines)
+    .rev()
+    .find(|&position| header[position].block_end == header[position].index)?;
+  let text = header[..=last]
+    .iter()
+    .map(|line| lines[line.index])
+    .collect::<Vec<_>>()
+    .join("\n");
+  detect_license(&text).map(|_| last)
+}
+
+/// Hashes of each prefix of the header's non-empty normalized lines, with the header position
+/// of the line ending each prefix.
+fn prefix_hashes(header: &[HeaderLine]) -> Vec<(u64, usize)> {
+  let mut hasher = DefaultHasher::new();
+  header
+    .iter()
+    .enumerate()
+    .filter(|(_, line)| !line.normalized.is_empty())
+    .map(|(position, line)| {
+      line.normalized.hash(&mut hasher);
+      (hasher.finish(), position)
+    })
+    .collect()
+}
+
+/// A header removed from one file.
+#[derive(Debug, Clone)]
+pub struct StrippedHeader {
+  /// SPDX identifier when the header contains a known license
+  pub license: Option<String>,
+  /// Number of lines removed
+  pub lines: usize,
+  /// The header as it appeared in the file
+  pub text: String,
+  /// Identifies the header across files: the license or the normalized text
+  key: String,
+}
+
+/// Counts of leading header prefixes across a set of files.
+#[derive(Debug, Clone)]
+pub struct HeaderIndex {
+  prefix_counts: HashMap<u64, usize>,
+  min_files: usize,
+}
+
+impl Default for HeaderIndex {
+  fn default() -> Self {
+    Self::new(Self::DEFAULT_MIN_FILES)
+  }
+}
+
+impl HeaderIndex {
+  /// A banner must start at least this many files to be removed.
+  pub const DEFAULT_MIN_FILES: usize = 3;
+
+  pub fn new(min_files: usize) -> Self {
+    Self {
+      prefix_counts: HashMap::new(),
+      min_files: min_files.max(2),
+    }
+  }
+
+  /// Builds an index over `files` (relative to `dir`), skipping unreadable files.
+  pub async fn from_files(dir: &Path, files: &[String]) -> Self {
+    let mut index = Self::default();
+    for file in files {
+      if let Ok(content) = fs::read_to_string(dir.join(file)).await {
+        index.observe(&content);
+      }
+    }
+    index
+  }
+
+  /// Records the header of one file.
+  pub fn observe(&mut self, content: &str) {
+    for (hash, _) in prefix_hashes(&header_lines(content)) {
+      *self.prefix_counts.entry(hash).or_default() += 1;
+    }
+  }
+
+  /// Blanks out the boilerplate header of `content`, if it has one. Lines are replaced by empty
+  /// lines so positions in the rest of the file stay valid.
+  pub fn strip(&self, content: &str) -> (String, Option<StrippedHeader>) {
+    let header = header_lines(content);
+    if header.is_empty() {
+      return (content.to_string(), None);
+    }
+
+    // Longest prefix shared by enough files
+    let shared_end = prefix_hashes(&header)
+      .into_iter()
+      .enumerate()
+      .filter(|(count, (hash, _))| {
+        *count + 1 >= MIN_PREFIX_LINES
+          && self.prefix_counts.get(hash).copied().unwrap_or(0) >= self.min_files
+      })
+      .map(|(_, (_, position))| position)
+      .next_back();
+
+    let lines: Vec<&str> = content.lines().collect();
+    let last_position = match (shared_end, license_notice_end(&header, &lines)) {
+      (Some(shared), Some(notice)) => shared.max(notice),
+      (Some(position), None) | (None, Some(position)) => position,
+      (None, None) => return (content.to_string(), None),
+    };
+
+    // Finish the enclosing block comment and swallow trailing separator lines
+    let mut end = header[last_position].block_end;
+    for line in &header[last_position + 1..] {
+      if line.index <= end || (line.normalized.is_empty() && line.index == end + 1) {
+        end = end.max(line.index);
+      } else {
+        break;
+      }
+    }
+
+    let start = header[0].index;
+    let removed: Vec<&str> = lines[start..=end].to_vec();
+    let license = detect_license(&removed.join("\n"));
+    let key = license.clone().unwrap_or_else(|| {
+      header
+        .iter()
+        .filter(|l| l.index <= end && !l.normalized.is_empty())
+        .map(|l| l.normalized.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+    });
+
+    let mut stripped: Vec<&str> = lines.clone();
+    for line in stripped.iter_mut().take(end + 1).skip(start) {
+      *line = "";
+    }
+    let mut result = stripped.join("\n");
+    if content.ends_with('\n') {
+      result.push('\n');
+    }
+
+    (
+      result,
+      Some(StrippedHeader {
+        license,
+        lines: removed.len(),
+        text: removed.join("\n"),
+        key,
+      }),
+    )
+  }
+}
+
+/// Tally of removed headers, rendered as a single note.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderSummary {
+  /// Distinct headers in first-seen order, with the number of files they were removed from
+  entries: Vec<(StrippedHeader, usize)>,
+}
+
+impl HeaderSummary {
+  pub fn record(&mut self, header: StrippedHeader) {
+    match self
+      .entries
+      .iter_mut()
+      .find(|(existing, _)| existing.key == header.key)
+    {
+      Some((_, files)) => *files += 1,
+      None => self.entries.push((header, 1)),
+    }
+  }
+
+  /// Total number of files a header was removed from.
+  pub fn files(&self) -> usize {
+    self.entries.iter().map(|(_, files)| files).sum()
+  }
+
+  /// Markdown note describing every removed header, or `None` if nothing was removed.
+  /// License headers are named by their SPDX identifier; other banners are shown once.
+  pub fn to_markdown(&self) -> Option<String> {
+    if self.entries.is_empty() {
+      return None;
+    }
+
+    let mut note = String::from("## Removed Boilerplate Headers\n\n");
+    for (header, files) in &self.entries {
+      let plural = if *files == 1 { "" } else { "s" };
+      match &header.license {
+        Some(license) => {
+          note.push_str(&format!(
+            "- `{}` license header, removed from {} file{}\n",
+            license, files, plural
+          ));
+        }
+        None => note.push_str(&format!(
+          "- {}-line banner, removed from {} file{}:\n\n~~~\n{}\n~~~\n\n",
+          header.lines, files, plural, header.text
+        )),
+      }
+    }
+    Some(note)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const APACHE: &str = "\
+/*
+ * Copyright 2023 Acme Corp
+ *
+ * Licensed under the Apache License, Version 2.0 (the \"License\");
+ * you may not use this file except in compliance with the License.
+ */
+package acme;";
+
+  #[test]
+  fn test_detect_license() {
+    assert_eq!(detect_license(APACHE).as_deref(), Some("Apache-2.0"));
+    assert_eq!(
+      detect_license("# SPDX-License-Identifier: MIT OR Apache-2.0").as_deref(),
+      Some("MIT OR Apache-2.0")
+    );
+    assert_eq!(
+      detect_license("GNU General Public License as published by the FSF; version 2").as_deref(),
+      Some("GPL-2.0")
+    );
+    assert_eq!(detect_license("// just a comment"), None);
+  }
+
+  #[test]
+  fn test_known_license_is_stripped_without_repetition() {
+    let index = HeaderIndex::default();
+    let (stripped, header) = index.strip(APACHE);
+    assert_eq!(stripped, "\n\n\n\n\n\npackage acme;");
+    let header = header.unwrap();
+    assert_eq!(header.license.as_deref(), Some("Apache-2.0"));
+    assert_eq!(header.lines, 6);
+  }
+
+  #[test]
+  fn test_shared_banner_prefix_across_files() {
+    let file = |year: u32, name: &str| {
+      format!(
+        "#!/usr/bin/env python\n# Copyright {} Acme Corp\n# Internal use only.\n# Do not distribute.\n# Module: {}\nimport os\n",
+        year, name
+      )
+    };
+    let files = [file(2021, "a"), file(2022, "b"), file(2024, "c")];
+    let mut index = HeaderIndex::default();
+    for content in &files {
+      index.observe(content);
+    }
+
+    let (stripped, header) = index.strip(&files[0]);
+    // The file-specific line after the shared prefix stays
+    assert_eq!(
+      stripped,
+      "#!/usr/bin/env python\n\n\n\n# Module: a\nimport os\n"
+    );
+    let header = header.unwrap();
+    assert_eq!(header.license, None);
+
+    // Not enough files share the banner
+    let mut sparse = HeaderIndex::default();
+    sparse.observe(&files[0]);
+    sparse.observe(&files[1]);
+    assert!(sparse.strip(&files[0]).1.is_none());
+
+    let mut summary = HeaderSummary::default();
+    for content in &files {
+      summary.record(index.strip(content).1.unwrap());
+    }
+    let (_, license_header) = HeaderIndex::default().strip(APACHE);
+    summary.record(license_header.unwrap());
+    assert_eq!(summary.files(), 4);
+    let note = summary.to_markdown().unwrap();
+    assert!(
+      note.contains("- 3-line banner, removed from 3 files:\n\n~~~\n# Copyright 2021 Acme Corp\n")
+    );
+    assert!(note.contains("- `Apache-2.0` license header, removed from 1 file\n"));
+  }
+
+  #[test]
+  fn test_doc_comments_and_code_are_not_headers() {
+    let mut index = HeaderIndex::default();
+    let rust = "//! Crate docs\n//! more\n//! and more\nfn main() {}";
+    for _ in 0..3 {
+      index.observe(rust);
+    }
+    assert!(index.strip(rust).1.is_none());
+    assert!(index.strip("#include <stdio.h>\nint x;").1.is_none());
+  }
+
+  #[test]
+  fn test_only_pure_license_notices_are_stripped() {
+    let index = HeaderIndex::default();
+    // Mentioning a license is not a license header
+    let mentions = "\
+// Checks vendored files for the GNU General Public License notice and
+// reports every file that carries one.
+fn check() {}";
+    assert!(index.strip(mentions).1.is_none());
+
+    // The notice goes, the module description after it stays
+    let mit = "\
+# Copyright (c) 2024 Acme
+#
+# Permission is hereby granted, free of charge, to any person obtaining a copy
+# of this software and associated documentation files (the \"Software\"), to deal
+#
+# Parses widget manifests.
+import os";
+    let (stripped, header) = index.strip(mit);
+    assert_eq!(header.unwrap().license.as_deref(), Some("MIT"));
+    assert_eq!(stripped, "\n\n\n\n\n# Parses widget manifests.\nimport os");
+
+    // A block comment mixing a notice with other text is kept whole
+    let mixed = "/*\n * Licensed under the Apache License, Version 2.0 (the \"License\");\n * Widget parser.\n */\nint x;";
+    assert!(index.strip(mixed).1.is_none());
+  }
+}
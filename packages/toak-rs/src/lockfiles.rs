@@ -0,0 +1,718 @@
+//! Dependency summaries of lockfiles. Lockfiles are excluded from the file listing, but the
+//! exact versions they pin are useful context, so `Cargo.lock`, `package-lock.json`,
+//! `bun.lock`, `poetry.lock` and `go.sum` are reduced to their direct and transitive
+//! dependencies with versions, leaving out hashes and URLs.
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::sync::OnceLock;
+use tokio::fs;
+
+/// Supported lockfile formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockfileKind {
+  Cargo,
+  Npm,
+  Bun,
+  Poetry,
+  GoSum,
+}
+
+impl LockfileKind {
+  /// Kind of lockfile at `path`, judged by its file name.
+  pub fn from_path(path: &Path) -> Option<Self> {
+    match path.file_name()?.to_str()? {
+      "Cargo.lock" => Some(Self::Cargo),
+      "package-lock.json" => Some(Self::Npm),
+      "bun.lock" => Some(Self::Bun),
+      "poetry.lock" => Some(Self::Poetry),
+      "go.sum" => Some(Self::GoSum),
+      _ => None,
+    }
+  }
+
+  /// What the dependencies are, for headings.
+  pub fn ecosystem(self) -> &'static str {
+    match self {
+      Self::Cargo => "Rust crates",
+      Self::Npm | Self::Bun => "npm packages",
+      Self::Poetry => "Python packages",
+      Self::GoSum => "Go modules",
+    }
+  }
+
+  /// Manifest next to the lockfile that declares the direct dependencies, when the lockfile
+  /// itself does not record them.
+  fn manifest(self) -> Option<&'static str> {
+    match self {
+      Self::Npm => Some("package.json"),
+      Self::Poetry => Some("pyproject.toml"),
+      Self::GoSum => Some("go.mod"),
+      Self::Cargo | Self::Bun => None,
+    }
+  }
+}
+
+/// A resolved dependency.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dependency {
+  pub name: String,
+  pub version: String,
+}
+
+/// Dependencies pinned by one lockfile.
+#[derive(Debug, Clone)]
+pub struct DependencySummary {
+  /// Path of the lockfile relative to the project root
+  pub lockfile: String,
+  pub kind: LockfileKind,
+  /// Dependencies declared by the project itself
+  pub direct: Vec<Dependency>,
+  /// Everything else the lockfile resolves
+  pub transitive: Vec<Dependency>,
+}
+
+impl DependencySummary {
+  /// Reads and summarizes the lockfile `lockfile` of the project in `dir`, together with its
+  /// manifest if the format needs one.
+  pub async fn load(dir: &Path, lockfile: &str) -> Result<Self> {
+    let path = dir.join(lockfile);
+    let kind = LockfileKind::from_path(&path)
+      .ok_or_else(|| anyhow!("{} is not a supported lockfile", lockfile))?;
+    let content = fs::read_to_string(&path).await?;
+    let manifest = match (kind.manifest(), path.parent()) {
+      (Some(name), Some(parent)) => fs::read_to_string(parent.join(name)).await.ok(),
+      _ => None,
+    };
+    Self::parse(lockfile, kind, &content, manifest.as_deref())
+  }
+
+  /// Summarizes lockfile `content`; `manifest` is the content of the sibling manifest, if any.
+  pub fn parse(
+    lockfile: &str,
+    kind: LockfileKind,
+    content: &str,
+    manifest: Option<&str>,
+  ) -> Result<Self> {
+    let (direct, transitive) = match kind {
+      LockfileKind::Cargo => parse_cargo_lock(content),
+      LockfileKind::Npm => parse_package_lock(content, manifest)?,
+      LockfileKind::Bun => parse_bun_lock(content)?,
+      LockfileKind::Poetry => parse_poetry_lock(content, manifest),
+      LockfileKind::GoSum => parse_go_sum(content, manifest),
+    };
+    let direct: BTreeSet<Dependency> = direct.into_iter().collect();
+    let transitive: BTreeSet<Dependency> = transitive
+      .into_iter()
+      .filter(|d| !direct.contains(d))
+      .collect();
+    Ok(Self {
+      lockfile: lockfile.to_string(),
+      kind,
+      direct: direct.into_iter().collect(),
+      transitive: transitive.into_iter().collect(),
+    })
+  }
+
+  /// Compact listing, e.g. `Direct (2): anyhow 1.0.86, serde 1.0.200`.
+  pub fn to_markdown(&self) -> String {
+    let list = |deps: &[Dependency]| {
+      deps
+        .iter()
+        .map(|d| format!("{} {}", d.name, d.version))
+        .collect::<Vec<_>>()
+        .join(", ")
+    };
+    let mut text = format!("### {} ({})\n", self.lockfile, self.kind.ecosystem());
+    text.push_str(&format!(
+      "Direct ({}): {}\n",
+      self.direct.len(),
+      list(&self.direct)
+    ));
+    text.push_str(&format!(
+      "Transitive ({}): {}\n",
+      self.transitive.len(),
+      list(&self.transitive)
+    ));
+    text
+  }
+}
+
+/// The "Dependencies" section of prompt.md from the listings of [`DependencySummary::to_markdown`],
+/// if any lockfile was summarized.
+pub fn dependencies_markdown(listings: &[String]) -> Option<String> {
+  if listings.is_empty() {
+    return None;
+  }
+  let mut markdown = String::from("## Dependencies\n\n");
+  for listing in listings {
+    markdown.push_str(listing);
+    markdown.push('\n');
+  }
+  Some(markdown)
+}
+
+type Dependencies = (Vec<Dependency>, Vec<Dependency>);
+
+/// A `[[package]]` table of a TOML lockfile.
+#[derive(Default)]
+struct TomlPackage {
+  fields: HashMap<String, Vec<String>>,
+  /// Keys of the `[package.dependencies]` subtable (poetry)
+  dependency_keys: Vec<String>,
+}
+
+impl TomlPackage {
+  fn field(&self, key: &str) -> Option<&str> {
+    self
+      .fields
+      .get(key)
+      .and_then(|v| v.first())
+      .map(String::as_str)
+  }
+}
+
+static TOML_STRING: OnceLock<Regex> = OnceLock::new();
+
+fn toml_strings(value: &str) -> Vec<String> {
+  let pattern =
+    TOML_STRING.get_or_init(|| Regex::new(r#""((?:[^"\\]|\\.)*)""#).expect("valid string pattern"));
+  pattern
+    .captures_iter(value)
+    .map(|c| c[1].to_string())
+    .collect()
+}
+
+fn toml_key(line: &str) -> Option<&str> {
+  let (key, _) = line.split_once('=')?;
+  Some(key.trim().trim_matches('"'))
+}
+
+/// Reads the `[[package]]` tables of a lockfile; string and array values only, which is all
+/// Cargo and poetry lockfiles use for names, versions and dependencies.
+fn toml_packages(content: &str) -> Vec<TomlPackage> {
+  #[derive(PartialEq)]
+  enum Section {
+    Package,
+    Dependencies,
+    Other,
+  }
+
+  let mut packages: Vec<TomlPackage> = Vec::new();
+  let mut section = Section::Other;
+  let mut lines = content.lines();
+  while let Some(line) = lines.next() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if line.starts_with('[') && !line.contains('=') {
+      section = match line {
+        "[[package]]" => {
+          packages.push(TomlPackage::default());
+          Section::Package
+        }
+        "[package.dependencies]" => Section::Dependencies,
+        _ => Section::Other,
+      };
+      continue;
+    }
+    let (Some(package), Some(key)) = (packages.last_mut(), toml_key(line)) else {
+      continue;
+    };
+    match section {
+      Section::Package => {
+        let mut value = line
+          .split_once('=')
+          .map(|(_, v)| v.trim().to_string())
+          .unwrap_or_default();
+        // Multi-line arrays run until the closing bracket
+        if value.starts_with('[') && !value.trim_end().ends_with(']') {
+          for next in lines.by_ref() {
+            value.push_str(next);
+            if next.trim_start().starts_with(']') {
+              break;
+            }
+          }
+        }
+        package.fields.insert(key.to_string(), toml_strings(&value));
+      }
+      Section::Dependencies => package.dependency_keys.push(key.to_string()),
+      Section::Other => {}
+    }
+  }
+  packages
+}
+
+fn parse_cargo_lock(content: &str) -> Dependencies {
+  let packages = toml_packages(content);
+  // Packages without a source are the workspace's own crates
+  let (members, external): (Vec<&TomlPackage>, Vec<&TomlPackage>) =
+    packages.iter().partition(|p| p.field("source").is_none());
+  let member_names: HashSet<&str> = members.iter().filter_map(|p| p.field("name")).collect();
+
+  // Entries are `name` or, when several versions are locked, `name version (source)`
+  let mut requested: HashSet<(String, Option<String>)> = HashSet::new();
+  for member in &members {
+    for entry in member.fields.get("dependencies").into_iter().flatten() {
+      let mut parts = entry.split_whitespace();
+      if let Some(name) = parts.next().filter(|n| !member_names.contains(n)) {
+        requested.insert((name.to_string(), parts.next().map(str::to_string)));
+      }
+    }
+  }
+
+  let mut direct = Vec::new();
+  let mut transitive = Vec::new();
+  for package in external {
+    let (Some(name), Some(version)) = (package.field("name"), package.field("version")) else {
+      continue;
+    };
+    let dependency = Dependency {
+      name: name.to_string(),
+      version: version.to_string(),
+    };
+    if requested.contains(&(name.to_string(), None))
+      || requested.contains(&(name.to_string(), Some(version.to_string())))
+    {
+      direct.push(dependency);
+    } else {
+      transitive.push(dependency);
+    }
+  }
+  (direct, transitive)
+}
+
+/// Names under the dependency fields of a package.json-style object.
+fn declared_dependencies(package: &Value) -> HashSet<String> {
+  [
+    "dependencies",
+    "devDependencies",
+    "optionalDependencies",
+    "peerDependencies",
+  ]
+  .iter()
+  .filter_map(|field| package.get(field).and_then(Value::as_object))
+  .flat_map(|deps| deps.keys().cloned())
+  .collect()
+}
+
+fn parse_package_lock(content: &str, manifest: Option<&str>) -> Result<Dependencies> {
+  let lock: Value =
+    serde_json::from_str(content).map_err(|e| anyhow!("Invalid package-lock.json: {}", e))?;
+  let mut direct = Vec::new();
+  let mut transitive = Vec::new();
+
+  if let Some(packages) = lock.get("packages").and_then(Value::as_object) {
+    // lockfileVersion 2 and 3: the root package is the "" entry
+    let declared = packages
+      .get("")
+      .map(declared_dependencies)
+      .unwrap_or_default();
+    for (key, package) in packages {
+      let Some((prefix, name)) = key.rsplit_once("node_modules/") else {
+        continue;
+      };
+      let Some(version) = package.get("version").and_then(Value::as_str) else {
+        continue;
+      };
+      let dependency = Dependency {
+        name: name.to_string(),
+        version: version.to_string(),
+      };
+      if prefix.is_empty() && declared.contains(name) {
+        direct.push(dependency);
+      } else {
+        transitive.push(dependency);
+      }
+    }
+  } else if let Some(dependencies) = lock.get("dependencies").and_then(Value::as_object) {
+    // lockfileVersion 1 only nests dependencies; direct ones come from package.json
+    let declared = manifest
+      .and_then(|m| serde_json::from_str::<Value>(m).ok())
+      .map(|m| declared_dependencies(&m))
+      .unwrap_or_default();
+    let mut stack: Vec<(&serde_json::Map<String, Value>, bool)> = vec![(dependencies, true)];
+    while let Some((dependencies, top_level)) = stack.pop() {
+      for (name, package) in dependencies {
+        if let Some(version) = package.get("version").and_then(Value::as_str) {
+          let dependency = Dependency {
+            name: name.clone(),
+            version: version.to_string(),
+          };
+          if top_level && declared.contains(name) {
+            direct.push(dependency);
+          } else {
+            transitive.push(dependency);
+          }
+        }
+        if let Some(nested) = package.get("dependencies").and_then(Value::as_object) {
+          stack.push((nested, false));
+        }
+      }
+    }
+  } else {
+    return Err(anyhow!(
+      "Unsupported package-lock.json: no packages or dependencies"
+    ));
+  }
+  Ok((direct, transitive))
+}
+
+static TRAILING_COMMA: OnceLock<Regex> = OnceLock::new();
+
+fn parse_bun_lock(content: &str) -> Result<Dependencies> {
+  // bun.lock is JSON with trailing commas
+  let trailing_comma = TRAILING_COMMA
+    .get_or_init(|| Regex::new(r",(\s*[}\]])").expect("valid trailing comma pattern"));
+  let lock: Value = serde_json::from_str(&trailing_comma.replace_all(content, "$1"))
+    .map_err(|e| anyhow!("Invalid bun.lock: {}", e))?;
+  let packages = lock
+    .get("packages")
+    .and_then(Value::as_object)
+    .ok_or_else(|| anyhow!("Unsupported bun.lock: no packages"))?;
+  let declared: HashSet<String> = lock
+    .get("workspaces")
+    .and_then(Value::as_object)
+    .map(|workspaces| {
+      workspaces
+        .values()
+        .flat_map(declared_dependencies)
+        .collect()
+    })
+    .unwrap_or_default();
+
+  let mut direct = Vec::new();
+  let mut transitive = Vec::new();
+  for (key, package) in packages {
+    // The first element is `name@version`; scoped names start with `@`
+    let Some((name, version)) = package
+      .get(0)
+      .and_then(Value::as_str)
+      .and_then(|id| id.rsplit_once('@'))
+    else {
+      continue;
+    };
+    if name.is_empty() || version.contains(':') {
+      // Workspace, link and file packages are the project's own code
+      continue;
+    }
+    let dependency = Dependency {
+      name: name.to_string(),
+      version: version.to_string(),
+    };
+    // Nested copies are keyed `parent/name`
+    if key == name && declared.contains(name) {
+      direct.push(dependency);
+    } else {
+      transitive.push(dependency);
+    }
+  }
+  Ok((direct, transitive))
+}
+
+/// Python distribution names compare case-insensitively with `-`, `_` and `.` equivalent.
+fn normalize_python_name(name: &str) -> String {
+  name.to_ascii_lowercase().replace(['_', '.'], "-")
+}
+
+static REQUIREMENT_NAME: OnceLock<Regex> = OnceLock::new();
+
+/// Direct dependencies declared in pyproject.toml, by Poetry tables or PEP 621 arrays.
+fn pyproject_dependencies(manifest: &str) -> HashSet<String> {
+  let requirement_name = REQUIREMENT_NAME.get_or_init(|| {
+    Regex::new(r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)").expect("valid requirement pattern")
+  });
+  let mut names = HashSet::new();
+  let mut table = String::new();
+  let mut in_array = false;
+
+  for line in manifest.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if line.starts_with('[') && !in_array {
+      table = line.trim_matches(['[', ']']).to_string();
+      continue;
+    }
+    let poetry_table = table == "tool.poetry.dependencies"
+      || table == "tool.poetry.dev-dependencies"
+      || (table.starts_with("tool.poetry.group.") && table.ends_with(".dependencies"));
+    let requirement_array = (table == "project"
+      && (in_array || toml_key(line) == Some("dependencies")))
+      || table == "project.optional-dependencies";
+
+    if poetry_table {
+      if let Some(key) = toml_key(line).filter(|k| *k != "python") {
+        names.insert(normalize_python_name(key));
+      }
+    } else if requirement_array {
+      let value = if in_array {
+        line
+      } else {
+        line.split_once('=').map_or("", |(_, v)| v)
+      };
+      for requirement in toml_strings(value) {
+        if let Some(name) = requirement_name.captures(&requirement) {
+          names.insert(normalize_python_name(&name[1]));
+        }
+      }
+      in_array = if in_array {
+        !line.contains(']')
+      } else {
+        value.contains('[') && !value.contains(']')
+      };
+    }
+  }
+  names
+}
+
+fn parse_poetry_lock(content: &str, manifest: Option<&str>) -> Dependencies {
+  let packages = toml_packages(content);
+  let declared = match manifest {
+    Some(manifest) => pyproject_dependencies(manifest),
+    // Without pyproject.toml, treat packages nothing else depends on as direct
+    None => {
+      let required: HashSet<String> = packages
+        .iter()
+        .flat_map(|p| p.dependency_keys.iter().map(|k| normalize_python_name(k)))
+        .collect();
+      packages
+        .iter()
+        .filter_map(|p| p.field("name").map(normalize_python_name))
+        .filter(|name| !required.contains(name))
+        .collect()
+    }
+  };
+
+  let mut direct = Vec::new();
+  let mut transitive = Vec::new();
+  for package in &packages {
+    let (Some(name), Some(version)) = (package.field("name"), package.field("version")) else {
+      continue;
+    };
+    let dependency = Dependency {
+      name: name.to_string(),
+      version: version.to_string(),
+    };
+    if declared.contains(&normalize_python_name(name)) {
+      direct.push(dependency);
+    } else {
+      transitive.push(dependency);
+    }
+  }
+  (direct, transitive)
+}
+
+/// Modules required in go.mod without an `// indirect` marker.
+fn go_mod_requirements(manifest: &str) -> HashSet<String> {
+  let mut names = HashSet::new();
+  let mut in_block = false;
+  for line in manifest.lines() {
+    let line = line.trim();
+    let requirement = if in_block {
+      if line.starts_with(')') {
+        in_block = false;
+        continue;
+      }
+      line
+    } else if let Some(rest) = line.strip_prefix("require") {
+      let rest = rest.trim();
+      if rest.starts_with('(') {
+        in_block = true;
+        continue;
+      }
+      rest
+    } else {
+      continue;
+    };
+    if !requirement.contains("// indirect") {
+      if let Some(module) = requirement
+        .split_whitespace()
+        .next()
+        .filter(|m| !m.starts_with("//"))
+      {
+        names.insert(module.to_string());
+      }
+    }
+  }
+  names
+}
+
+fn parse_go_sum(content: &str, manifest: Option<&str>) -> Dependencies {
+  let declared = manifest.map(go_mod_requirements).unwrap_or_default();
+  let mut direct = Vec::new();
+  let mut transitive = Vec::new();
+  for line in content.lines() {
+    let mut fields = line.split_whitespace();
+    let (Some(module), Some(version)) = (fields.next(), fields.next()) else {
+      continue;
+    };
+    // `/go.mod` entries only pin module graphs, not code that is built
+    if version.ends_with("/go.mod") {
+      continue;
+    }
+    let dependency = Dependency {
+      name: module.to_string(),
+      version: version.to_string(),
+    };
+    if declared.contains(module) {
+      direct.push(dependency);
+    } else {
+      transitive.push(dependency);
+    }
+  }
+  (direct, transitive)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names(deps: &[Dependency]) -> Vec<String> {
+    deps
+      .iter()
+      .map(|d| format!("{} {}", d.name, d.version))
+      .collect()
+  }
+
+  #[test]
+  fn test_cargo_lock() {
+    let lock = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde",
+ "syn 2.0.1",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "ddc6f9cc94d67c0e21aaf7eda3a010fd3af78ebf6e096aa6e2e13c79749cce4f"
+dependencies = [
+ "serde_derive",
+]
+
+[[package]]
+name = "serde_derive"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "1.0.109"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "2.0.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+    let summary = DependencySummary::parse("Cargo.lock", LockfileKind::Cargo, lock, None).unwrap();
+    assert_eq!(names(&summary.direct), vec!["serde 1.0.200", "syn 2.0.1"]);
+    assert_eq!(
+      names(&summary.transitive),
+      vec!["serde_derive 1.0.200", "syn 1.0.109"]
+    );
+    let markdown = summary.to_markdown();
+    assert_eq!(
+      markdown,
+      "### Cargo.lock (Rust crates)\nDirect (2): serde 1.0.200, syn 2.0.1\nTransitive (2): serde_derive 1.0.200, syn 1.0.109\n"
+    );
+    assert!(!markdown.contains("checksum") && !markdown.contains("https://"));
+  }
+
+  #[test]
+  fn test_npm_and_bun_lockfiles() {
+    let npm = r#"{
+      "lockfileVersion": 3,
+      "packages": {
+        "": {"name": "app", "dependencies": {"react": "^18.0.0"}, "devDependencies": {"@types/node": "^20"}},
+        "node_modules/react": {"version": "18.3.1", "resolved": "https://registry.npmjs.org/react/-/react-18.3.1.tgz", "integrity": "sha512-abc"},
+        "node_modules/@types/node": {"version": "20.1.0"},
+        "node_modules/loose-envify": {"version": "1.4.0"},
+        "node_modules/react/node_modules/loose-envify": {"version": "1.3.0"}
+      }
+    }"#;
+    let summary =
+      DependencySummary::parse("package-lock.json", LockfileKind::Npm, npm, None).unwrap();
+    assert_eq!(
+      names(&summary.direct),
+      vec!["@types/node 20.1.0", "react 18.3.1"]
+    );
+    assert_eq!(
+      names(&summary.transitive),
+      vec!["loose-envify 1.3.0", "loose-envify 1.4.0"]
+    );
+
+    let bun = r#"{
+      "lockfileVersion": 1,
+      "workspaces": {
+        "": {"name": "app", "dependencies": {"zod": "^3.23.0",},},
+      },
+      "packages": {
+        "zod": ["zod@3.23.8", "", {}, "sha512-xyz"],
+        "@scope/util": ["@scope/util@0.2.0", "", {}, "sha512-def"],
+        "app-lib": ["app-lib@workspace:packages/lib"],
+      },
+    }"#;
+    let summary = DependencySummary::parse("bun.lock", LockfileKind::Bun, bun, None).unwrap();
+    assert_eq!(names(&summary.direct), vec!["zod 3.23.8"]);
+    assert_eq!(names(&summary.transitive), vec!["@scope/util 0.2.0"]);
+
+    assert!(DependencySummary::parse("package-lock.json", LockfileKind::Npm, "{}", None).is_err());
+  }
+
+  #[test]
+  fn test_poetry_lock_and_go_sum() {
+    let lock = r#"
+[[package]]
+name = "Requests"
+version = "2.32.3"
+files = [
+    {file = "requests-2.32.3-py3-none-any.whl", hash = "sha256:70761cfe"},
+]
+
+[package.dependencies]
+urllib3 = ">=1.21.1,<3"
+
+[[package]]
+name = "urllib3"
+version = "2.2.2"
+
+[metadata]
+content-hash = "abc"
+"#;
+    let pyproject = "[project]\nname = \"app\"\ndependencies = [\n  \"requests>=2.31\",\n]\n";
+    let summary =
+      DependencySummary::parse("poetry.lock", LockfileKind::Poetry, lock, Some(pyproject)).unwrap();
+    assert_eq!(names(&summary.direct), vec!["Requests 2.32.3"]);
+    assert_eq!(names(&summary.transitive), vec!["urllib3 2.2.2"]);
+    let inferred =
+      DependencySummary::parse("poetry.lock", LockfileKind::Poetry, lock, None).unwrap();
+    assert_eq!(names(&inferred.direct), vec!["Requests 2.32.3"]);
+
+    let go_sum = "\
+github.com/google/uuid v1.6.0 h1:NIvaJDMOsjHA8n1jAhLSgzrAzy1Hgr+hNrb57e+94F0=
+github.com/google/uuid v1.6.0/go.mod h1:TIyPZe4MgqvfeYDBFedMoGGpEw/LqOeaOT+nhxU+yHo=
+golang.org/x/sys v0.20.0 h1:Od9JTbYCk261bKm4M/mw7AklTlFYIa0bIp9BgSm1S8Y=
+golang.org/x/text v0.3.0/go.mod h1:NqM8EUOU14njkJ3fqMW+pc6Ldnwhi/IjpwHt7yyuwOQ=
+";
+    let go_mod = "module example.com/app\n\nrequire (\n\tgithub.com/google/uuid v1.6.0\n\tgolang.org/x/sys v0.20.0 // indirect\n)\n";
+    let summary =
+      DependencySummary::parse("go.sum", LockfileKind::GoSum, go_sum, Some(go_mod)).unwrap();
+    assert_eq!(
+      names(&summary.direct),
+      vec!["github.com/google/uuid v1.6.0"]
+    );
+    assert_eq!(names(&summary.transitive), vec!["golang.org/x/sys v0.20.0"]);
+  }
+}
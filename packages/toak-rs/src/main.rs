@@ -1,16 +1,16 @@
 mod cli;
-mod markdown_generator;
-mod token_cleaner;
-mod embeddings_generator;
-mod text_chunker;
-mod json_database_generator;
-mod semantic_search;
 
 use clap::Parser;
-use cli::{Args, Commands};
-use markdown_generator::{MarkdownGenerator, MarkdownGeneratorOptions};
-use json_database_generator::{JsonDatabaseGenerator, JsonDatabaseOptions};
-use semantic_search::SemanticSearch;
+use cli::{Args, Commands, ModelCommand};
+use toak_rs::{
+  export_model, fetch_model, hashing_model_id, max_input_length, model_cache_dir, model_id,
+  ChunkerConfig, ChunkingStrategy, CleaningOptions, CommentMode, DataSummaryOptions,
+  EmbeddingBackendConfig, EmbeddingModel, FingerprintKey, InjectionOptions, InjectionRules, JsonDatabaseGenerator,
+  JsonDatabaseOptions, LanguageStripping, MarkdownGenerator, MarkdownGeneratorOptions, PiiKind,
+  PiiOptions, Pseudonymizer, RedactionMode, RedactionOptions, RedactionReport, ScanFormat,
+  ScanOptions, SecretAllowlist, SecretScanner, SemanticSearch, Severity, SourceSpan,
+  StrippingOptions, DEFAULT_HASHING_DIMENSION, DEFAULT_MODEL,
+};
 
 #[tokio::main]
 async fn main() {
@@ -25,28 +25,136 @@ async fn main() {
       output_file_path,
       quiet,
       prompt: _,
+      redaction_report,
+      pseudonymize_secrets,
+      redacted_lines,
+      redact_pii,
+      pii_types,
+      pii_allow_domain,
+      unicode,
+      prompt_injection,
+      keep_imports_for,
+      doc_comments,
+      embedding_doc_comments,
+      keep_license_headers,
+      data_summary_threshold,
+      data_summary_records,
+      no_data_summary,
+      no_dependency_summary,
+      chunking,
+      no_context_headers,
+      embedding,
     } => {
-      run_generate(dir, output_file_path, quiet).await;
+      let pii = (redact_pii || !pii_types.is_empty()).then(|| {
+        let kinds = if pii_types.is_empty() { PiiKind::ALL.to_vec() } else { pii_types };
+        let mut pii = PiiOptions::only(&kinds);
+        pii.allowed_email_domains.extend(pii_allow_domain);
+        pii
+      });
+      let cleaning = CleaningOptions {
+        unicode,
+        redaction: RedactionOptions {
+          mode: if pseudonymize_secrets {
+            // A single pseudonymizer is shared so placeholders match between prompt.md and embeddings
+            RedactionMode::Pseudonymize(Pseudonymizer::new())
+          } else {
+            RedactionMode::Mask
+          },
+          line_policy: redacted_lines,
+          ..Default::default()
+        },
+        pii,
+        stripping: StrippingOptions {
+          comments: doc_comments,
+          language_stripping: LanguageStripping {
+            disabled: keep_imports_for.into_iter().collect(),
+          },
+          keep_prose: false,
+        },
+        injection: prompt_injection.map(|policy| InjectionOptions {
+          policy,
+          rules: InjectionRules::default(),
+        }),
+      };
+      run_generate(
+        dir,
+        output_file_path,
+        quiet,
+        redaction_report,
+        cleaning,
+        ContentOptions {
+          embedding_doc_comments,
+          strip_boilerplate_headers: !keep_license_headers,
+          data_summary: (!no_data_summary).then_some(DataSummaryOptions {
+            threshold_bytes: data_summary_threshold,
+            sample_records: data_summary_records,
+          }),
+          dependency_summary: !no_dependency_summary,
+          chunking,
+          context_headers: !no_context_headers,
+          embedding_backend: embedding.backend().unwrap_or_default(),
+        },
+      )
+      .await;
+    }
+    Commands::Scan {
+      dir,
+      format,
+      severity_threshold,
+      output,
+      update_baseline,
+    } => {
+      run_scan(dir, format, severity_threshold, output, update_baseline).await;
     }
     Commands::Search {
       query,
       embeddings_file,
       top_n,
       full,
+      embedding,
     } => {
-      run_search(&query, &embeddings_file, top_n, full);
+      run_search(&query, &embeddings_file, top_n, full, embedding.backend());
     }
+    Commands::Model { command } => match command {
+      ModelCommand::List => run_model_list(),
+      ModelCommand::Fetch { model } => run_model_fetch(model.unwrap_or(DEFAULT_MODEL)),
+      ModelCommand::Export { model, output } => run_model_export(model.unwrap_or(DEFAULT_MODEL), &output),
+    },
   }
 }
 
+/// Content shaping shared by prompt.md and the embeddings database.
+struct ContentOptions {
+  embedding_doc_comments: CommentMode,
+  strip_boilerplate_headers: bool,
+  data_summary: Option<DataSummaryOptions>,
+  dependency_summary: bool,
+  chunking: ChunkingStrategy,
+  context_headers: bool,
+  embedding_backend: EmbeddingBackendConfig,
+}
+
 async fn run_generate(
   dir: Option<std::path::PathBuf>,
   output_file_path: Option<std::path::PathBuf>,
   quiet: bool,
+  redaction_report: Option<std::path::PathBuf>,
+  mut cleaning: CleaningOptions,
+  content: ContentOptions,
 ) {
   println!("RUNNING TOKENIZER");
 
   let dir = dir.unwrap_or_else(|| std::path::PathBuf::from("."));
+  if let Some(injection) = cleaning.injection.as_mut() {
+    injection.rules = load_injection_rules(&dir);
+  }
+  // A report persists fingerprints, so it needs the key they are computed under
+  let report_key = redaction_report
+    .as_ref()
+    .map(|_| load_fingerprint_key(&dir, 1));
+  if let Some(key) = &report_key {
+    cleaning.redaction.fingerprint_key = Some(key.clone());
+  }
   let output_file_path = output_file_path.unwrap_or_else(|| std::path::PathBuf::from("prompt.md"));
   let verbose = !quiet;
 
@@ -57,6 +165,10 @@ async fn run_generate(
     file_type_exclusions: Default::default(),
     file_exclusions: Default::default(),
     verbose,
+    cleaning: cleaning.clone(),
+    strip_boilerplate_headers: content.strip_boilerplate_headers,
+    data_summary: content.data_summary.clone(),
+    dependency_summary: content.dependency_summary,
   };
 
   let mut markdown_generator = MarkdownGenerator::new(markdown_options);
@@ -67,6 +179,10 @@ async fn run_generate(
         eprintln!("Markdown generation failed");
         std::process::exit(1);
       }
+      if let (Some(report_path), Some(key)) = (redaction_report, report_key) {
+        save_fingerprint_key(&key, &dir, 1);
+        write_redaction_report(&report_path, RedactionReport::new(result.redaction_findings));
+      }
     }
     Err(e) => {
       eprintln!("Error generating markdown: {}", e);
@@ -84,11 +200,27 @@ async fn run_generate(
     file_type_exclusions: Default::default(),
     file_exclusions: Default::default(),
     verbose,
-    chunker_config: Default::default(),
+    chunker_config: ChunkerConfig {
+      strategy: content.chunking,
+      ..Default::default()
+    },
     max_concurrent_files: 4, // Process up to 4 files concurrently
+    embedding_backend: content.embedding_backend,
     // Use defaults for embedding pool; override here if desired
     embedding_pool_size: JsonDatabaseOptions::default().embedding_pool_size,
     embedding_batch_size: None,
+    cleaning: CleaningOptions {
+      stripping: StrippingOptions {
+        comments: content.embedding_doc_comments,
+        keep_prose: true,
+        ..cleaning.stripping
+      },
+      ..cleaning
+    },
+    strip_boilerplate_headers: content.strip_boilerplate_headers,
+    data_summary: content.data_summary,
+    dependency_summary: content.dependency_summary,
+    context_headers: content.context_headers,
   };
 
   let json_generator = match JsonDatabaseGenerator::new(json_options) {
@@ -106,9 +238,21 @@ async fn run_generate(
         std::process::exit(1);
       }
       println!(
-        "Successfully generated embeddings for {} files ({} chunks)",
-        result.total_files, result.total_chunks
+        "Successfully generated embeddings for {} files ({} chunks at {} locations)",
+        result.total_files, result.total_chunks, result.total_locations
       );
+      if result.truncated_chunks > 0 {
+        println!(
+          "{} chunks were longer than the embedding model's maximum input length and were truncated",
+          result.truncated_chunks
+        );
+      }
+      if result.duplicate_chunks > 0 {
+        println!(
+          "{} duplicate chunks were stored once and share an embedding",
+          result.duplicate_chunks
+        );
+      }
     }
     Err(e) => {
       eprintln!("Error generating embeddings: {}", e);
@@ -119,9 +263,183 @@ async fn run_generate(
   println!("\n✓ All tasks completed successfully!");
 }
 
-fn run_search(query: &str, embeddings_file: &std::path::Path, top_n: usize, full: bool) {
-  // Load the semantic search engine
-  let mut search = match SemanticSearch::new(embeddings_file) {
+fn load_injection_rules(dir: &std::path::Path) -> InjectionRules {
+  match InjectionRules::load(dir) {
+    Ok(rules) => rules,
+    Err(e) => {
+      eprintln!("Error loading prompt injection rules: {}", e);
+      std::process::exit(2);
+    }
+  }
+}
+
+fn load_fingerprint_key(dir: &std::path::Path, exit_code: i32) -> FingerprintKey {
+  FingerprintKey::load_or_default(dir).unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    std::process::exit(exit_code);
+  })
+}
+
+/// Writes the key next to the fingerprints being persisted, so later runs can match them.
+fn save_fingerprint_key(key: &FingerprintKey, dir: &std::path::Path, exit_code: i32) {
+  if let Err(e) = key.save(dir) {
+    eprintln!("{}", e);
+    std::process::exit(exit_code);
+  }
+}
+
+fn write_redaction_report(path: &std::path::Path, report: RedactionReport) {
+  let json = match serde_json::to_string_pretty(&report) {
+    Ok(json) => json,
+    Err(e) => {
+      eprintln!("Error serializing redaction report: {}", e);
+      std::process::exit(1);
+    }
+  };
+
+  if let Err(e) = std::fs::write(path, json) {
+    eprintln!("Error writing redaction report to {}: {}", path.display(), e);
+    std::process::exit(1);
+  }
+
+  println!(
+    "Redaction report with {} findings written to {}",
+    report.total_findings,
+    path.display()
+  );
+}
+
+async fn run_scan(
+  dir: Option<std::path::PathBuf>,
+  format: ScanFormat,
+  severity_threshold: Severity,
+  output: Option<std::path::PathBuf>,
+  update_baseline: bool,
+) {
+  let dir = dir.unwrap_or_else(|| std::path::PathBuf::from("."));
+  let fingerprint_key = load_fingerprint_key(&dir, 2);
+  let scanner = SecretScanner::new(ScanOptions {
+    dir: dir.clone(),
+    file_type_exclusions: Default::default(),
+    file_exclusions: Default::default(),
+    verbose: false,
+    allowlist: SecretAllowlist::default(),
+    fingerprint_key: Some(fingerprint_key.clone()),
+    // The new baseline must list every current finding, including ones the old baseline hid
+    ignore_baseline: update_baseline,
+  });
+
+  // Exit code 2 distinguishes scan failures from findings above the threshold
+  let result = match scanner.scan().await {
+    Ok(result) => result,
+    Err(e) => {
+      eprintln!("Error scanning for secrets: {}", e);
+      std::process::exit(2);
+    }
+  };
+
+  if update_baseline {
+    let baseline_path = dir.join(SecretAllowlist::BASELINE_FILE);
+    if let Some(parent) = baseline_path.parent() {
+      if let Err(e) = std::fs::create_dir_all(parent) {
+        eprintln!("Error creating {}: {}", parent.display(), e);
+        std::process::exit(2);
+      }
+    }
+    save_fingerprint_key(&fingerprint_key, &dir, 2);
+    let baseline = RedactionReport::new(result.findings);
+    let written = serde_json::to_string_pretty(&baseline)
+      .map_err(|e| e.to_string())
+      .and_then(|json| std::fs::write(&baseline_path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+      eprintln!("Error writing secrets baseline to {}: {}", baseline_path.display(), e);
+      std::process::exit(2);
+    }
+    println!(
+      "Secrets baseline with {} fingerprints written to {}",
+      baseline.total_findings,
+      baseline_path.display()
+    );
+    return;
+  }
+
+  let rendered = match result.render(format) {
+    Ok(rendered) => rendered,
+    Err(e) => {
+      eprintln!("Error rendering scan results: {}", e);
+      std::process::exit(2);
+    }
+  };
+
+  match output {
+    Some(path) => {
+      if let Err(e) = std::fs::write(&path, rendered) {
+        eprintln!("Error writing scan report to {}: {}", path.display(), e);
+        std::process::exit(2);
+      }
+    }
+    None => print!("{}", rendered),
+  }
+
+  if result.exceeds(severity_threshold) {
+    std::process::exit(1);
+  }
+}
+
+fn run_model_list() {
+  let default_model = model_id(&DEFAULT_MODEL);
+  let mut models = fastembed::TextEmbedding::list_supported_models();
+  models.sort_by_key(|info| info.model_code.to_lowercase());
+  for info in models {
+    let marker = if info.model_code == default_model { " (default)" } else { "" };
+    println!("{}{}\n  {} dimensions, {} tokens: {}", info.model_code, marker, info.dim, max_input_length(&info.model), info.description);
+  }
+  println!(
+    "{}\n  {} dimensions, no model: deterministic feature hashing for tests and keyword search (any dimension)",
+    hashing_model_id(DEFAULT_HASHING_DIMENSION),
+    DEFAULT_HASHING_DIMENSION
+  );
+}
+
+fn run_model_fetch(model: EmbeddingModel) {
+  let cache_dir = model_cache_dir();
+  match fetch_model(&model, &cache_dir) {
+    Ok(files) => println!("Fetched {} ({} files) into {}", model_id(&model), files.len(), cache_dir.display()),
+    Err(e) => {
+      eprintln!("Error fetching {}: {:#}", model_id(&model), e);
+      std::process::exit(1);
+    }
+  }
+}
+
+fn run_model_export(model: EmbeddingModel, output: &std::path::Path) {
+  match export_model(&model, &model_cache_dir(), output) {
+    Ok(manifest) => {
+      println!("Exported {} to {}", manifest.model, output.display());
+      println!("Copy the directory to the offline machine and pass it with --model-dir or TOAK_MODEL_DIR.");
+    }
+    Err(e) => {
+      eprintln!("Error exporting {}: {:#}", model_id(&model), e);
+      std::process::exit(1);
+    }
+  }
+}
+
+/// `path:line` when the line is known, else just the path.
+fn format_location(file_path: &str, span: Option<&SourceSpan>) -> String {
+  match span {
+    Some(span) => format!("{}:{}", file_path, span.start.line),
+    None => file_path.to_string(),
+  }
+}
+
+fn run_search(query: &str, embeddings_file: &std::path::Path, top_n: usize, full: bool, backend: Option<EmbeddingBackendConfig>) {
+  // Load the semantic search engine, with the database's own model unless another was chosen
+  let search = match &backend {
+    Some(backend) => SemanticSearch::with_backend_config(embeddings_file, backend),
+    None => SemanticSearch::new(embeddings_file),
+  };
+  let mut search = match search {
     Ok(search) => search,
     Err(e) => {
       eprintln!("Error loading embeddings database: {}", e);
@@ -132,7 +450,10 @@ fn run_search(query: &str, embeddings_file: &std::path::Path, top_n: usize, full
 
   // Display search info
   let metadata = search.metadata();
-  println!("Searching {} chunks from {} files", metadata.total_chunks, metadata.total_files);
+  println!(
+    "Searching {} chunks at {} locations from {} files (model: {})",
+    metadata.total_chunks, metadata.total_locations, metadata.total_files, metadata.model
+  );
   println!("Query: \"{}\"\n", query);
 
   // Perform the search
@@ -143,6 +464,14 @@ fn run_search(query: &str, embeddings_file: &std::path::Path, top_n: usize, full
       std::process::exit(1);
     }
   };
+  if search.metadata().normalization_mismatch {
+    let normalized = metadata.normalized == Some(true);
+    eprintln!(
+      "[warn] query embedding is {}normalized but the database's are {}normalized; check that both use the same model",
+      if normalized { "not " } else { "" },
+      if normalized { "" } else { "not " }
+    );
+  }
 
   if results.is_empty() {
     println!("No results found.");
@@ -154,7 +483,19 @@ fn run_search(query: &str, embeddings_file: &std::path::Path, top_n: usize, full
   println!("{}", "=".repeat(80));
 
   for (i, result) in results.iter().enumerate() {
-    println!("\n{}. {} (similarity: {:.4})", i + 1, result.file_path, result.similarity);
+    let location = format_location(&result.file_path, result.span.as_ref());
+    match &result.heading_path {
+      Some(heading_path) => println!("\n{}. {} [{}] (similarity: {:.4})", i + 1, location, heading_path, result.similarity),
+      None => println!("\n{}. {} (similarity: {:.4})", i + 1, location, result.similarity),
+    }
+    if !result.duplicates.is_empty() {
+      let duplicates: Vec<String> = result
+        .duplicates
+        .iter()
+        .map(|duplicate| format_location(&duplicate.file_path, duplicate.metadata.span.as_ref()))
+        .collect();
+      println!("   Also in: {}", duplicates.join(", "));
+    }
     println!("{}", "-".repeat(80));
 
     if full {
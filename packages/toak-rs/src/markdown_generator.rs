@@ -1,6 +1,10 @@
 //! Utilities that turn a repository into a human readable markdown file, handling ignore files
 //! and ensuring the generated artifacts are tracked in `.gitignore`.
-use crate::token_cleaner::{clean_and_redact, count_tokens};
+use crate::license_headers::{HeaderIndex, HeaderSummary, StrippedHeader};
+use crate::data_summarizer::{summarize_data_file, DataSummaryOptions};
+use crate::lockfiles::{dependencies_markdown, DependencySummary, LockfileKind};
+use crate::notebook::{extract_notebook, is_notebook, NotebookOptions};
+use crate::token_cleaner::{clean_and_redact_with_options, count_tokens, CleaningOptions, FingerprintKey, RedactionFinding};
 use anyhow::{anyhow, Result};
 use regex::Regex;
 use std::collections::HashSet;
@@ -94,6 +98,13 @@ const DEFAULT_FILE_EXCLUSIONS: &[&str] = &[
   "**/*.log",
 ];
 
+/// Whether `exclusion` is one of the built-in file type or pattern exclusions. Lockfile
+/// summaries skip them: they keep raw lockfiles out, while a summary is only worth leaving out
+/// when the user or a .aiignore file asks for it.
+pub(crate) fn is_default_exclusion(exclusion: &str) -> bool {
+  DEFAULT_FILE_TYPE_EXCLUSIONS.contains(&exclusion) || DEFAULT_FILE_EXCLUSIONS.contains(&exclusion)
+}
+
 /// Configuration that controls how markdown is generated.
 pub struct MarkdownGeneratorOptions {
   pub dir: PathBuf,
@@ -101,6 +112,15 @@ pub struct MarkdownGeneratorOptions {
   pub file_type_exclusions: HashSet<String>,
   pub file_exclusions: Vec<String>,
   pub verbose: bool,
+  /// How file content is sanitized, redacted and cleaned
+  pub cleaning: CleaningOptions,
+  /// Remove license headers and banners repeated across files, noting them once at the top
+  pub strip_boilerplate_headers: bool,
+  /// Replace large JSON, CSV, YAML and SQL files by their structure and a few sample records;
+  /// `None` includes them whole
+  pub data_summary: Option<DataSummaryOptions>,
+  /// Summarize lockfiles into a "Dependencies" section instead of including or excluding them
+  pub dependency_summary: bool,
 }
 
 impl Default for MarkdownGeneratorOptions {
@@ -117,6 +137,10 @@ impl Default for MarkdownGeneratorOptions {
         .map(|s| s.to_string())
         .collect(),
       verbose: true,
+      cleaning: CleaningOptions::default(),
+      strip_boilerplate_headers: true,
+      data_summary: Some(DataSummaryOptions::default()),
+      dependency_summary: true,
     }
   }
 }
@@ -126,6 +150,9 @@ pub struct MarkdownGenerator {
   options: MarkdownGeneratorOptions,
   file_exclusions: Vec<String>,
   initialized: bool,
+  redaction_findings: Vec<RedactionFinding>,
+  header_index: HeaderIndex,
+  header_summary: HeaderSummary,
 }
 
 impl MarkdownGenerator {
@@ -134,6 +161,9 @@ impl MarkdownGenerator {
       file_exclusions: options.file_exclusions.clone(),
       options,
       initialized: false,
+      redaction_findings: Vec::new(),
+      header_index: HeaderIndex::default(),
+      header_summary: HeaderSummary::default(),
     }
   }
 
@@ -202,20 +232,18 @@ impl MarkdownGenerator {
     Ok(())
   }
 
-  /// Initializes the generator by loading ignore files
+  /// Initializes the generator by loading ignore files and the project's secret suppressions
   async fn initialize(&mut self) -> Result<()> {
     if !self.initialized {
       self.load_nested_ignore_files().await?;
+      self.options.cleaning.redaction.load_project_files(&self.options.dir)?;
       self.initialized = true;
     }
     Ok(())
   }
 
-  /// Gets tracked files from git, applying exclusions
-  async fn get_tracked_files(&mut self) -> Result<Vec<String>> {
-    self.initialize().await?;
-
-    // Run git ls-files
+  /// Lists every file tracked by git
+  fn git_ls_files(&self) -> Result<Vec<String>> {
     let output = Command::new("git")
       .arg("ls-files")
       .current_dir(&self.options.dir)
@@ -235,6 +263,15 @@ impl MarkdownGenerator {
       .map(|s| s.to_string())
       .collect();
 
+    Ok(tracked_files)
+  }
+
+  /// Gets tracked files from git, applying exclusions
+  pub(crate) async fn get_tracked_files(&mut self) -> Result<Vec<String>> {
+    self.initialize().await?;
+
+    let tracked_files = self.git_ls_files()?;
+
     if self.options.verbose {
       println!("Total tracked files: {}", tracked_files.len());
     }
@@ -245,20 +282,9 @@ impl MarkdownGenerator {
     let filtered_files = tracked_files
       .into_iter()
       .filter(|file| {
-        let path = Path::new(file);
-        let ext = path
-          .extension()
-          .and_then(|e| e.to_str())
-          .map(|e| format!(".{}", e))
-          .unwrap_or_default();
-
-        // Check if file type is excluded
-        if self.options.file_type_exclusions.contains(&ext) {
-          return false;
-        }
-
-        // Check if file matches exclusion patterns
-        !self.matches_exclusion_patterns(file)
+        // Lockfiles are summarized separately
+        let summarized = self.options.dependency_summary && LockfileKind::from_path(Path::new(file)).is_some();
+        !summarized && !self.is_excluded(file)
       })
       .collect::<Vec<_>>();
 
@@ -273,6 +299,37 @@ impl MarkdownGenerator {
     Ok(filtered_files)
   }
 
+  /// Checks if a tracked file is excluded by its type, the exclusion patterns or .aiignore files
+  fn is_excluded(&self, file: &str) -> bool {
+    let path = Path::new(file);
+    let ext = path
+      .extension()
+      .and_then(|e| e.to_str())
+      .map(|e| format!(".{}", e))
+      .unwrap_or_default();
+
+    // Fingerprints could be confirmed by anyone holding the key
+    self.options.file_type_exclusions.contains(&ext)
+      || path.ends_with(FingerprintKey::KEY_FILE)
+      || self.matches_exclusion_patterns(file)
+  }
+
+  /// Checks if a lockfile's dependency summary is excluded, by exclusions other than the
+  /// built-in ones
+  fn is_lockfile_excluded(&self, file: &str) -> bool {
+    let ext = Path::new(file)
+      .extension()
+      .and_then(|e| e.to_str())
+      .map(|e| format!(".{}", e))
+      .unwrap_or_default();
+
+    (self.options.file_type_exclusions.contains(&ext) && !is_default_exclusion(&ext))
+      || self
+        .file_exclusions
+        .iter()
+        .any(|pattern| !is_default_exclusion(pattern) && self.glob_match(pattern, file))
+  }
+
   /// Checks if a file path matches any exclusion patterns
   fn matches_exclusion_patterns(&self, file: &str) -> bool {
     for pattern in &self.file_exclusions {
@@ -304,8 +361,14 @@ impl MarkdownGenerator {
     OCR_FILE_TYPES.contains(&ext)
   }
 
-  /// Reads and processes file content, using OCR for supported image/PDF types on macOS
-  async fn read_file_content(&self, file_path: &Path) -> Result<String> {
+  /// Reads and processes file content, using OCR for supported image/PDF types on macOS.
+  /// Returns the cleaned content together with any secrets redacted from it and the
+  /// boilerplate header removed from it.
+  async fn read_file_content(
+    &self,
+    file_path: &Path,
+    relative_path: &str,
+  ) -> Result<(String, Vec<RedactionFinding>, Option<StrippedHeader>)> {
     #[cfg(target_os = "macos")]
     {
       let ext = file_path
@@ -315,19 +378,36 @@ impl MarkdownGenerator {
         .unwrap_or_default();
 
       if Self::is_ocr_file(&ext) {
-        return self.read_file_content_ocr(file_path).await;
+        return Ok((self.read_file_content_ocr(file_path).await?, Vec::new(), None));
       }
     }
 
-    let content = fs::read_to_string(file_path).await?;
-    let cleaned = clean_and_redact(&content);
+    let mut content = fs::read_to_string(file_path).await?;
+    if is_notebook(file_path) {
+      content = extract_notebook(&content, &NotebookOptions::default())?;
+    }
+    if let Some(summary) = self
+      .options
+      .data_summary
+      .as_ref()
+      .and_then(|options| summarize_data_file(file_path, &content, options))
+    {
+      content = summary;
+    }
+    let (content, header) = if self.options.strip_boilerplate_headers {
+      self.header_index.strip(&content)
+    } else {
+      (content, None)
+    };
+    let (cleaned, findings) =
+      clean_and_redact_with_options(&content, relative_path, &self.options.cleaning);
 
     if self.options.verbose && !cleaned.is_empty() {
       let token_count = count_tokens(&cleaned);
       println!("{}: Tokens[{}]", file_path.display(), token_count);
     }
 
-    Ok(cleaned.trim_end().to_string())
+    Ok((cleaned.trim_end().to_string(), findings, header))
   }
 
   /// Reads file content via OCR (macOS only)
@@ -359,11 +439,20 @@ impl MarkdownGenerator {
     }
 
     let mut markdown = String::from("# Project Files\n\n");
+    self.redaction_findings.clear();
+    self.header_summary = HeaderSummary::default();
+    if self.options.strip_boilerplate_headers {
+      self.header_index = HeaderIndex::from_files(&self.options.dir, &tracked_files).await;
+    }
 
     for file in tracked_files {
       let absolute_path = self.options.dir.join(&file);
-      match self.read_file_content(&absolute_path).await {
-        Ok(content) => {
+      match self.read_file_content(&absolute_path, &file).await {
+        Ok((content, findings, header)) => {
+          self.redaction_findings.extend(findings);
+          if let Some(header) = header {
+            self.header_summary.record(header);
+          }
           if !content.trim().is_empty() {
             markdown.push_str(&format!("## {}\n~~~\n{}\n~~~\n\n", file, content.trim()));
           } else if self.options.verbose {
@@ -378,9 +467,52 @@ impl MarkdownGenerator {
       }
     }
 
+    if self.options.dependency_summary {
+      let summaries = self.summarize_lockfiles().await?;
+      if let Some(section) = dependencies_markdown(&summaries) {
+        markdown.insert_str("# Project Files\n\n".len(), &section);
+        if self.options.verbose {
+          println!("Summarized dependencies from {} lockfiles", summaries.len());
+        }
+      }
+    }
+
+    // A single note replaces the headers removed from every file
+    if let Some(note) = self.header_summary.to_markdown() {
+      markdown.insert_str("# Project Files\n\n".len(), &format!("{}\n", note));
+      if self.options.verbose {
+        println!("Removed boilerplate headers from {} files", self.header_summary.files());
+      }
+    }
+
     Ok(markdown)
   }
 
+  /// Summarizes every tracked lockfile that is not excluded, skipping those that fail to parse,
+  /// and cleans the listings like file contents
+  async fn summarize_lockfiles(&mut self) -> Result<Vec<String>> {
+    let mut summaries = Vec::new();
+    for file in self.git_ls_files()? {
+      if LockfileKind::from_path(Path::new(&file)).is_none() || self.is_lockfile_excluded(&file) {
+        continue;
+      }
+      match DependencySummary::load(&self.options.dir, &file).await {
+        Ok(summary) => {
+          let (cleaned, findings) =
+            clean_and_redact_with_options(&summary.to_markdown(), &file, &self.options.cleaning);
+          self.redaction_findings.extend(findings);
+          summaries.push(cleaned);
+        }
+        Err(e) => {
+          if self.options.verbose {
+            eprintln!("Error summarizing lockfile {}: {}", file, e);
+          }
+        }
+      }
+    }
+    Ok(summaries)
+  }
+
   /// Reads the todo file, creating it if it doesn't exist
   async fn get_todo(&self) -> Result<String> {
     let todo_path = self.options.dir.join("todo");
@@ -494,6 +626,30 @@ impl MarkdownGenerator {
         self.options.output_file_path.display()
       );
       println!("{{ \"total_tokens\": {} }}", token_count);
+      let count = |prefix: &str| {
+        self
+          .redaction_findings
+          .iter()
+          .filter(|f| f.rule_id.starts_with(prefix))
+          .count()
+      };
+      let unicode_findings = count("unicode-");
+      let injection_findings = count("injection-");
+      let pii_findings = count("pii-");
+      println!(
+        "Redacted {} secrets",
+        self.redaction_findings.len() - unicode_findings - injection_findings - pii_findings
+      );
+      if self.options.cleaning.pii.is_some() {
+        println!("Redacted {} personal data values", pii_findings);
+      }
+      println!("Found {} suspicious Unicode sequences", unicode_findings);
+      if self.options.cleaning.injection.is_some() {
+        println!("Flagged {} possible prompt injections", injection_findings);
+        for finding in self.redaction_findings.iter().filter(|f| f.rule_id.starts_with("injection-")) {
+          println!("  {}:{} ({})", finding.file_path, finding.line, finding.rule_id);
+        }
+      }
     }
 
     fs::write(&self.options.output_file_path, &markdown).await?;
@@ -502,6 +658,7 @@ impl MarkdownGenerator {
       success: true,
       token_count: Some(token_count),
       error: None,
+      redaction_findings: self.redaction_findings.clone(),
     })
   }
 }
@@ -513,4 +670,6 @@ pub struct MarkdownResult {
   pub success: bool,
   pub token_count: Option<usize>,
   pub error: Option<String>,
+  /// Every secret redacted from the files included in the document
+  pub redaction_findings: Vec<RedactionFinding>,
 }
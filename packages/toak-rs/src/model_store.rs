@@ -0,0 +1,216 @@
+//! Model directories for machines without network access. `toak model fetch` downloads a model
+//! of the fastembed catalog on a connected machine, `toak model export` copies its ONNX weights
+//! and tokenizer files into a self-contained directory with a [`ModelManifest`], and the
+//! directory is loaded with fastembed's user-defined model API, without hf-hub or a token.
+use anyhow::{bail, Context, Result};
+use fastembed::{
+  get_cache_dir, EmbeddingModel, Pooling, TextEmbedding, TokenizerFiles, UserDefinedEmbeddingModel,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::embeddings_generator::{find_model, max_input_length, model_id};
+
+/// Name of the manifest file in a model directory.
+pub const MANIFEST_FILE: &str = "toak-model.json";
+
+/// Tokenizer files every model directory holds next to the manifest.
+const TOKENIZER_FILES: [&str; 4] = [
+  "tokenizer.json",
+  "config.json",
+  "special_tokens_map.json",
+  "tokenizer_config.json",
+];
+
+/// Describes the model in a model directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelManifest {
+  /// Identifier recorded in embeddings databases, the catalog code for catalog models
+  pub model: String,
+  /// Length of the embedding vectors
+  pub dimension: usize,
+  /// Maximum input length in tokens
+  pub max_length: usize,
+  /// The ONNX model, relative to the directory
+  pub model_file: String,
+  /// Files holding weights stored outside the ONNX model, relative to the directory
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub external_data: Vec<String>,
+  /// How token embeddings are pooled, `mean` or `cls`; catalog models use their own pooling
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub pooling: Option<String>,
+}
+
+impl ModelManifest {
+  /// Reads the manifest of the model directory `dir`.
+  pub fn load(dir: &Path) -> Result<Self> {
+    let path = dir.join(MANIFEST_FILE);
+    let contents = std::fs::read_to_string(&path).with_context(|| {
+      format!(
+        "{} is not a model directory: cannot read {}",
+        dir.display(),
+        path.display()
+      )
+    })?;
+    serde_json::from_str(&contents)
+      .with_context(|| format!("invalid model manifest {}", path.display()))
+  }
+}
+
+/// Where fastembed caches downloaded models: `FASTEMBED_CACHE_DIR` if set, else
+/// `.fastembed_cache`. Models fetched there are found by `--model` without downloading again.
+pub fn model_cache_dir() -> PathBuf {
+  PathBuf::from(get_cache_dir())
+}
+
+/// Downloads the files of a catalog model into `cache_dir`, or finds them there, and returns
+/// each file's name in the model repository with its local path.
+pub fn fetch_model(model: &EmbeddingModel, cache_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+  let info = TextEmbedding::get_model_info(model)?;
+  let endpoint =
+    std::env::var("HF_ENDPOINT").unwrap_or_else(|_| "https://huggingface.co".to_string());
+  let api = hf_hub::api::sync::ApiBuilder::new()
+    .with_cache_dir(cache_dir.to_path_buf())
+    .with_endpoint(endpoint)
+    .with_progress(true)
+    .build()?;
+  let repo = api.model(info.model_code.clone());
+
+  std::iter::once(&info.model_file)
+    .chain(&info.additional_files)
+    .map(String::as_str)
+    .chain(TOKENIZER_FILES)
+    .map(|file| {
+      let path = repo
+        .get(file)
+        .with_context(|| format!("failed to fetch {} of {}", file, info.model_code))?;
+      Ok((file.to_string(), path))
+    })
+    .collect()
+}
+
+/// Copies a catalog model into `output` as a model directory, fetching it into `cache_dir`
+/// first if needed. Files keep their place in the model repository, since ONNX models refer to
+/// their external data by relative path.
+pub fn export_model(
+  model: &EmbeddingModel,
+  cache_dir: &Path,
+  output: &Path,
+) -> Result<ModelManifest> {
+  let info = TextEmbedding::get_model_info(model)?;
+  for (file, source) in fetch_model(model, cache_dir)? {
+    let destination = output.join(&file);
+    if let Some(parent) = destination.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(&source, &destination).with_context(|| {
+      format!(
+        "failed to copy {} to {}",
+        source.display(),
+        destination.display()
+      )
+    })?;
+  }
+
+  let manifest = ModelManifest {
+    model: model_id(model),
+    dimension: info.dim,
+    max_length: max_input_length(model),
+    model_file: info.model_file.clone(),
+    external_data: info.additional_files.clone(),
+    pooling: TextEmbedding::get_default_pooling_method(model)
+      .map(|pooling| pooling_name(&pooling).to_string()),
+  };
+  std::fs::write(
+    output.join(MANIFEST_FILE),
+    serde_json::to_string_pretty(&manifest)?,
+  )?;
+  Ok(manifest)
+}
+
+fn pooling_name(pooling: &Pooling) -> &'static str {
+  match pooling {
+    Pooling::Cls => "cls",
+    Pooling::Mean => "mean",
+  }
+}
+
+fn parse_pooling(name: &str) -> Result<Pooling> {
+  match name.to_ascii_lowercase().as_str() {
+    "cls" => Ok(Pooling::Cls),
+    "mean" => Ok(Pooling::Mean),
+    _ => bail!("unknown pooling {}, expected mean or cls", name),
+  }
+}
+
+/// Reads the model directory `dir` into a user-defined fastembed model. Catalog models get the
+/// pooling, quantization and output of their catalog entry.
+pub fn read_model_dir(dir: &Path) -> Result<(ModelManifest, UserDefinedEmbeddingModel)> {
+  let manifest = ModelManifest::load(dir)?;
+  let read = |file: &str| {
+    let path = dir.join(file);
+    std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+  };
+  let tokenizer_files = TokenizerFiles {
+    tokenizer_file: read("tokenizer.json")?,
+    config_file: read("config.json")?,
+    special_tokens_map_file: read("special_tokens_map.json")?,
+    tokenizer_config_file: read("tokenizer_config.json")?,
+  };
+  for file in &manifest.external_data {
+    if !dir.join(file).is_file() {
+      bail!("model directory {} is missing {}", dir.display(), file);
+    }
+  }
+
+  let mut model = UserDefinedEmbeddingModel::new(read(&manifest.model_file)?, tokenizer_files);
+  if let Some(catalog) = find_model(&manifest.model) {
+    model.pooling = TextEmbedding::get_default_pooling_method(&catalog);
+    model.quantization = TextEmbedding::get_quantization_mode(&catalog);
+    model.output_key = TextEmbedding::get_model_info(&catalog)?.output_key.clone();
+  }
+  if let Some(pooling) = &manifest.pooling {
+    model.pooling = Some(parse_pooling(pooling)?);
+  }
+  Ok((manifest, model))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_reads_model_directory() {
+    let dir = std::env::temp_dir().join(format!("toak-model-dir-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("onnx")).unwrap();
+    let manifest = ModelManifest {
+      model: "Xenova/bge-small-en-v1.5".to_string(),
+      dimension: 384,
+      max_length: 512,
+      model_file: "onnx/model.onnx".to_string(),
+      external_data: vec!["onnx/model.onnx_data".to_string()],
+      pooling: None,
+    };
+    std::fs::write(
+      dir.join(MANIFEST_FILE),
+      serde_json::to_string(&manifest).unwrap(),
+    )
+    .unwrap();
+    std::fs::write(dir.join("onnx/model.onnx"), b"onnx").unwrap();
+    for file in TOKENIZER_FILES {
+      std::fs::write(dir.join(file), b"{}").unwrap();
+    }
+
+    let error = read_model_dir(&dir).unwrap_err().to_string();
+    assert!(error.contains("missing onnx/model.onnx_data"), "{}", error);
+
+    std::fs::write(dir.join("onnx/model.onnx_data"), b"weights").unwrap();
+    let (loaded, model) = read_model_dir(&dir).unwrap();
+    assert_eq!(loaded, manifest);
+    assert_eq!(model.onnx_file, b"onnx");
+    // The catalog entry of the model decides its pooling
+    assert_eq!(model.pooling, Some(Pooling::Cls));
+
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
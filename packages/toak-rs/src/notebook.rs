@@ -0,0 +1,319 @@
+//! Extraction of Jupyter notebooks (`.ipynb`) into plain text: markdown and code cells in
+//! order, each labeled with its cell number, so neither base64 outputs nor notebook JSON reach
+//! the cleaner.
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::source_map::{LineIndex, SourceSpan};
+
+/// How notebook cells are rendered.
+#[derive(Debug, Clone)]
+pub struct NotebookOptions {
+  /// Include text outputs of code cells; rich outputs such as images are always replaced by a
+  /// placeholder
+  pub include_outputs: bool,
+  /// Text outputs longer than this many characters are truncated
+  pub max_output_chars: usize,
+}
+
+impl Default for NotebookOptions {
+  fn default() -> Self {
+    Self {
+      include_outputs: true,
+      max_output_chars: 500,
+    }
+  }
+}
+
+impl NotebookOptions {
+  /// Cells only, no outputs.
+  pub fn without_outputs() -> Self {
+    Self {
+      include_outputs: false,
+      ..Default::default()
+    }
+  }
+}
+
+/// Whether `path` is a Jupyter notebook.
+pub fn is_notebook(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|e| e.to_str())
+    .is_some_and(|e| e.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Notebook text fields are either a string or a list of lines.
+fn joined_text(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    Value::Array(parts) => parts.iter().filter_map(Value::as_str).collect(),
+    _ => String::new(),
+  }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+  let text = text.trim_end();
+  match text.char_indices().nth(max_chars) {
+    Some((cut, _)) => format!("{}\n[... output truncated]", &text[..cut]),
+    None => text.to_string(),
+  }
+}
+
+/// Text rendering of one code cell output.
+fn render_output(output: &Value, options: &NotebookOptions) -> Option<String> {
+  match output.get("output_type")?.as_str()? {
+    "stream" => Some(truncate(
+      &joined_text(output.get("text")?),
+      options.max_output_chars,
+    )),
+    "execute_result" | "display_data" => {
+      let data = output.get("data")?.as_object()?;
+      match data.get("text/plain") {
+        Some(text) => Some(truncate(&joined_text(text), options.max_output_chars)),
+        None => data
+          .keys()
+          .next()
+          .map(|mime| format!("[{} output omitted]", mime)),
+      }
+    }
+    "error" => Some(format!(
+      "{}: {}",
+      output
+        .get("ename")
+        .and_then(Value::as_str)
+        .unwrap_or("Error"),
+      output
+        .get("evalue")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+    )),
+    _ => None,
+  }
+}
+
+/// A notebook rendered as text, with the line of the notebook file each text line came from.
+pub(crate) struct NotebookText {
+  pub(crate) text: String,
+  /// 0-based line in the notebook file of every line of `text`
+  file_lines: Vec<usize>,
+}
+
+impl NotebookText {
+  /// Moves a span of lines of the text to the lines of the notebook file they came from,
+  /// `index` being the file's line index.
+  pub(crate) fn file_span(&self, span: &SourceSpan, index: &LineIndex) -> SourceSpan {
+    let file_line = |line: usize| {
+      let position = line
+        .saturating_sub(1)
+        .min(self.file_lines.len().saturating_sub(1));
+      self.file_lines.get(position).copied().unwrap_or(0)
+    };
+    let (start, end) = (file_line(span.start.line), file_line(span.end.line));
+    SourceSpan {
+      start: index.position(start, 0),
+      end: index.position(end, index.line(end).len()),
+    }
+  }
+}
+
+/// Finds the cells' `source` fields in the notebook file, in order, and returns for every cell
+/// the file line of each line of its source. Sources that cannot be found map to the line of
+/// the last one found.
+fn source_lines(json: &str, cells: &[Value]) -> Vec<Vec<usize>> {
+  let index = LineIndex::new(json);
+  let line_of = |offset: usize| index.position_at(offset).line - 1;
+  let mut cursor = 0;
+
+  cells
+    .iter()
+    .map(|cell| {
+      // The key, not a string value that happens to read "source"
+      while let Some(found) = json[cursor..].find("\"source\"") {
+        cursor += found + "\"source\"".len();
+        if json[cursor..].trim_start().starts_with(':') {
+          break;
+        }
+      }
+      let parts: Vec<&str> = match cell.get("source") {
+        Some(Value::String(text)) => vec![text.as_str()],
+        Some(Value::Array(parts)) => parts.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+      };
+
+      let mut lines = Vec::new();
+      let mut at_line_start = true;
+      for part in parts {
+        let encoded = serde_json::to_string(part).unwrap_or_default();
+        if let Some(found) = json[cursor..].find(&encoded) {
+          cursor += found;
+        }
+        let file_line = line_of(cursor);
+        for c in part.chars() {
+          if at_line_start {
+            lines.push(file_line);
+          }
+          at_line_start = c == '\n';
+        }
+      }
+      if lines.is_empty() {
+        lines.push(line_of(cursor));
+      }
+      lines
+    })
+    .collect()
+}
+
+/// Renders an nbformat 4 notebook as labeled cells, e.g. `# %% [code] cell 2`.
+pub fn extract_notebook(json: &str, options: &NotebookOptions) -> Result<String> {
+  extract_notebook_lines(json, options).map(|notebook| notebook.text)
+}
+
+/// Like [`extract_notebook`], keeping the notebook file line of every line of the text. Labels
+/// and outputs take the line where their cell's source starts.
+pub(crate) fn extract_notebook_lines(
+  json: &str,
+  options: &NotebookOptions,
+) -> Result<NotebookText> {
+  let notebook: Value =
+    serde_json::from_str(json).map_err(|e| anyhow!("Invalid notebook JSON: {}", e))?;
+  let cells = notebook
+    .get("cells")
+    .and_then(Value::as_array)
+    .ok_or_else(|| {
+      anyhow!("Unsupported notebook format: no top-level cells (nbformat 4 required)")
+    })?;
+  let cell_lines = source_lines(json, cells);
+
+  let mut sections: Vec<(String, Vec<usize>)> = Vec::new();
+  for (idx, cell) in cells.iter().enumerate() {
+    let cell_type = cell
+      .get("cell_type")
+      .and_then(Value::as_str)
+      .unwrap_or("raw");
+    let source = cell.get("source").map(joined_text).unwrap_or_default();
+    if source.trim().is_empty() {
+      continue;
+    }
+    let source = source.trim_end();
+    let lines = &cell_lines[idx];
+    let mut file_lines = vec![lines[0]];
+    file_lines.extend(
+      (0..source.lines().count()).map(|i| lines.get(i).copied().unwrap_or(lines[lines.len() - 1])),
+    );
+    sections.push((
+      format!("# %% [{}] cell {}\n{}", cell_type, idx + 1, source),
+      file_lines,
+    ));
+
+    if options.include_outputs && cell_type == "code" {
+      let outputs: Vec<String> = cell
+        .get("outputs")
+        .and_then(Value::as_array)
+        .map(|outputs| {
+          outputs
+            .iter()
+            .filter_map(|o| render_output(o, options))
+            .collect()
+        })
+        .unwrap_or_default();
+      let outputs: Vec<String> = outputs
+        .into_iter()
+        .filter(|o| !o.trim().is_empty())
+        .collect();
+      if !outputs.is_empty() {
+        let output = format!("# %% [output] cell {}\n{}", idx + 1, outputs.join("\n"));
+        let file_lines = vec![cell_lines[idx][0]; output.lines().count()];
+        sections.push((output, file_lines));
+      }
+    }
+  }
+
+  let mut text = Vec::new();
+  let mut file_lines = Vec::new();
+  for (section, lines) in sections {
+    // The blank line between sections belongs to the one before
+    if let Some(&last) = file_lines.last() {
+      file_lines.push(last);
+    }
+    text.push(section);
+    file_lines.extend(lines);
+  }
+  Ok(NotebookText {
+    text: text.join("\n\n"),
+    file_lines,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const NOTEBOOK: &str = r##"{
+    "nbformat": 4,
+    "cells": [
+      {"cell_type": "markdown", "source": ["# Analysis\n", "Load the data."]},
+      {"cell_type": "code", "source": "df = load()\ndf.head()", "outputs": [
+        {"output_type": "stream", "text": ["loading...\n"]},
+        {"output_type": "display_data", "data": {"image/png": "iVBORw0KGgoAAAANSUhEUg=="}},
+        {"output_type": "execute_result", "data": {"text/plain": ["0123456789abcdef"]}}
+      ]},
+      {"cell_type": "code", "source": [], "outputs": []},
+      {"cell_type": "code", "source": "1 / 0", "outputs": [
+        {"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero", "traceback": []}
+      ]}
+    ]
+  }"##;
+
+  #[test]
+  fn test_extracts_cells_in_order_with_truncated_outputs() {
+    let options = NotebookOptions {
+      max_output_chars: 10,
+      ..Default::default()
+    };
+    let text = extract_notebook(NOTEBOOK, &options).unwrap();
+    assert_eq!(
+      text,
+      "# %% [markdown] cell 1\n# Analysis\nLoad the data.\n\n\
+# %% [code] cell 2\ndf = load()\ndf.head()\n\n\
+# %% [output] cell 2\nloading...\n[image/png output omitted]\n0123456789\n[... output truncated]\n\n\
+# %% [code] cell 4\n1 / 0\n\n\
+# %% [output] cell 4\nZeroDivisionError: division by zero"
+    );
+    assert!(!text.contains("iVBOR"));
+  }
+
+  #[test]
+  fn test_without_outputs_and_invalid_input() {
+    let text = extract_notebook(NOTEBOOK, &NotebookOptions::without_outputs()).unwrap();
+    assert!(!text.contains("[output]"));
+    assert!(text.contains("# %% [code] cell 4\n1 / 0"));
+
+    assert!(extract_notebook("not json", &NotebookOptions::default()).is_err());
+    assert!(extract_notebook(r#"{"worksheets": []}"#, &NotebookOptions::default()).is_err());
+    assert!(is_notebook(Path::new("analysis/Report.IPYNB")));
+  }
+
+  #[test]
+  fn test_text_lines_map_to_notebook_lines() {
+    let notebook = extract_notebook_lines(NOTEBOOK, &NotebookOptions::without_outputs()).unwrap();
+    assert_eq!(notebook.text.lines().count(), notebook.file_lines.len());
+    let index = LineIndex::new(NOTEBOOK);
+    let text_index = LineIndex::new(&notebook.text);
+    let file_line = |text_line: usize| {
+      let span = notebook.file_span(&text_index.lines_span(text_line, text_line + 1), &index);
+      index.line(span.start.line - 1).trim().to_string()
+    };
+
+    // `Load the data.` is the second source line of the first cell
+    assert!(
+      file_line(2).contains("\"Load the data.\""),
+      "{}",
+      file_line(2)
+    );
+    // Lines of a source given as one string share its line
+    assert!(file_line(5).starts_with("{\"cell_type\": \"code\", \"source\": \"df = load()"));
+    assert!(file_line(8).contains("\"source\": \"1 / 0\""));
+  }
+}
@@ -0,0 +1,278 @@
+//! Detection of personal data (emails, IP addresses, phone numbers) for the optional PII
+//! redaction stage. Matches are replaced by typed placeholders such as `[EMAIL]` so the
+//! surrounding text keeps its shape.
+use crate::token_cleaner::Severity;
+use regex::Regex;
+use std::net::Ipv4Addr;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// Kinds of personal data the PII stage can redact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PiiKind {
+  Email,
+  Ipv4,
+  Ipv6,
+  Phone,
+}
+
+impl PiiKind {
+  pub const ALL: [PiiKind; 4] = [PiiKind::Email, PiiKind::Ipv4, PiiKind::Ipv6, PiiKind::Phone];
+
+  /// Placeholder written in place of the value.
+  pub fn placeholder(self) -> &'static str {
+    match self {
+      Self::Email => "[EMAIL]",
+      Self::Ipv4 => "[IPV4]",
+      Self::Ipv6 => "[IPV6]",
+      Self::Phone => "[PHONE]",
+    }
+  }
+
+  /// Severity recorded on findings of this kind.
+  pub fn severity(self) -> Severity {
+    match self {
+      Self::Email | Self::Phone => Severity::Medium,
+      Self::Ipv4 | Self::Ipv6 => Severity::Low,
+    }
+  }
+
+  /// Rule identifier used in redaction findings.
+  pub fn rule_id(self) -> &'static str {
+    match self {
+      Self::Email => "pii-email",
+      Self::Ipv4 => "pii-ipv4",
+      Self::Ipv6 => "pii-ipv6",
+      Self::Phone => "pii-phone",
+    }
+  }
+}
+
+impl std::str::FromStr for PiiKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "email" => Ok(Self::Email),
+      "ipv4" => Ok(Self::Ipv4),
+      "ipv6" => Ok(Self::Ipv6),
+      "phone" => Ok(Self::Phone),
+      other => Err(format!(
+        "unknown PII type '{}' (expected email, ipv4, ipv6 or phone)",
+        other
+      )),
+    }
+  }
+}
+
+/// Configuration for the PII redaction stage.
+#[derive(Debug, Clone)]
+pub struct PiiOptions {
+  pub emails: bool,
+  pub ipv4: bool,
+  pub ipv6: bool,
+  pub phone_numbers: bool,
+  /// Email domains that are never redacted; subdomains are included
+  pub allowed_email_domains: Vec<String>,
+}
+
+impl Default for PiiOptions {
+  fn default() -> Self {
+    Self {
+      emails: true,
+      ipv4: true,
+      ipv6: true,
+      phone_numbers: true,
+      allowed_email_domains: ["example.com", "example.org", "example.net"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+    }
+  }
+}
+
+impl PiiOptions {
+  /// Options with only the given kinds enabled and the default domain allowlist.
+  pub fn only(kinds: &[PiiKind]) -> Self {
+    Self {
+      emails: kinds.contains(&PiiKind::Email),
+      ipv4: kinds.contains(&PiiKind::Ipv4),
+      ipv6: kinds.contains(&PiiKind::Ipv6),
+      phone_numbers: kinds.contains(&PiiKind::Phone),
+      ..Default::default()
+    }
+  }
+
+  fn enabled(&self, kind: PiiKind) -> bool {
+    match kind {
+      PiiKind::Email => self.emails,
+      PiiKind::Ipv4 => self.ipv4,
+      PiiKind::Ipv6 => self.ipv6,
+      PiiKind::Phone => self.phone_numbers,
+    }
+  }
+
+  fn allows_email(&self, email: &str) -> bool {
+    let domain = email
+      .rsplit('@')
+      .next()
+      .unwrap_or_default()
+      .to_ascii_lowercase();
+    self.allowed_email_domains.iter().any(|allowed| {
+      let allowed = allowed.trim_start_matches('@').to_ascii_lowercase();
+      domain == allowed || domain.ends_with(&format!(".{}", allowed))
+    })
+  }
+}
+
+/// A detected piece of personal data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+  pub kind: PiiKind,
+  pub range: Range<usize>,
+}
+
+static PII_PATTERNS: OnceLock<Vec<(PiiKind, Regex)>> = OnceLock::new();
+
+fn get_pii_patterns() -> &'static Vec<(PiiKind, Regex)> {
+  PII_PATTERNS.get_or_init(|| {
+    vec![
+      (PiiKind::Email, r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}"),
+      // Boundaries are checked by `is_version_like`, since `\b` accepts `1.2.3.4.5`
+      (PiiKind::Ipv4, r"\b(?:\d{1,3}\.){3}\d{1,3}\b"),
+      (
+        PiiKind::Ipv6,
+        r"(?i)\b(?:[0-9a-f]{1,4}:){7}[0-9a-f]{1,4}\b|\b(?:[0-9a-f]{1,4}:){1,6}(?::[0-9a-f]{1,4}){1,6}\b",
+      ),
+      // International (+CC ...) or North American style numbers with separators
+      (
+        PiiKind::Phone,
+        r"\+\d{1,3}[\s.-]?\(?\d{1,4}\)?(?:[\s.-]?\d{2,4}){2,4}\b|\(?\b\d{3}\)?[\s.-]\d{3}[\s.-]\d{4}\b",
+      ),
+    ]
+    .into_iter()
+    .filter_map(|(kind, pattern)| Regex::new(pattern).ok().map(|re| (kind, re)))
+    .collect()
+  })
+}
+
+/// Checks a candidate against rules the regexes cannot express.
+fn is_valid(kind: PiiKind, value: &str, options: &PiiOptions) -> bool {
+  match kind {
+    PiiKind::Email => !options.allows_email(value),
+    // Loopback and unspecified addresses identify nobody
+    PiiKind::Ipv4 => value
+      .parse::<Ipv4Addr>()
+      .map(|ip| !ip.is_loopback() && !ip.is_unspecified())
+      .unwrap_or(false),
+    PiiKind::Ipv6 => {
+      value.parse::<std::net::Ipv6Addr>().is_ok() && value.chars().any(|c| c.is_ascii_digit())
+    }
+    PiiKind::Phone => {
+      let digits = value.chars().filter(|c| c.is_ascii_digit()).count();
+      (10..=15).contains(&digits)
+    }
+  }
+}
+
+/// Whether an IPv4 candidate at `range` is part of a longer dotted number or identifier, or
+/// reads as a version: after a version operator or on a line mentioning a version, as in
+/// manifests and lockfiles.
+fn is_version_like(text: &str, range: &Range<usize>) -> bool {
+  let joined = |c: Option<char>| c.is_some_and(|c| c == '.' || c == '_' || c.is_alphanumeric());
+  if joined(text[..range.start].chars().next_back()) || joined(text[range.end..].chars().next()) {
+    return true;
+  }
+
+  let before = &text[..range.start];
+  let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+  ["==", ">=", "<=", "~=", "^", "~"]
+    .iter()
+    .any(|operator| line.ends_with(operator))
+    || line.to_ascii_lowercase().contains("version")
+}
+
+/// Finds all enabled PII in `text`, ordered by position. Overlapping candidates are resolved
+/// in favour of the kind listed first (emails before IPs before phone numbers).
+pub fn detect_pii(text: &str, options: &PiiOptions) -> Vec<PiiMatch> {
+  let mut matches: Vec<PiiMatch> = Vec::new();
+
+  for (kind, re) in get_pii_patterns() {
+    if !options.enabled(*kind) {
+      continue;
+    }
+    for m in re.find_iter(text) {
+      let overlaps = matches
+        .iter()
+        .any(|existing| existing.range.start < m.end() && m.start() < existing.range.end);
+      let range = m.range();
+      if *kind == PiiKind::Ipv4 && is_version_like(text, &range) {
+        continue;
+      }
+      if !overlaps && is_valid(*kind, m.as_str(), options) {
+        matches.push(PiiMatch { kind: *kind, range });
+      }
+    }
+  }
+
+  matches.sort_by_key(|m| m.range.start);
+  matches
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kinds(text: &str, options: &PiiOptions) -> Vec<(PiiKind, String)> {
+    detect_pii(text, options)
+      .into_iter()
+      .map(|m| (m.kind, text[m.range].to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn test_detects_each_kind() {
+    let text =
+      "contact jane.doe@corp.io from 10.0.12.7 or fe80::1ff:fe23:4567:890a, call +1 415 555 0100";
+    let found = kinds(text, &PiiOptions::default());
+    assert_eq!(
+      found,
+      vec![
+        (PiiKind::Email, "jane.doe@corp.io".to_string()),
+        (PiiKind::Ipv4, "10.0.12.7".to_string()),
+        (PiiKind::Ipv6, "fe80::1ff:fe23:4567:890a".to_string()),
+        (PiiKind::Phone, "+1 415 555 0100".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_skips_allowed_and_non_pii_values() {
+    let text = "a@example.com b@mail.example.org 127.0.0.1 999.1.1.1 std::io::Result version 1.2.3";
+    assert!(detect_pii(text, &PiiOptions::default()).is_empty());
+  }
+
+  #[test]
+  fn test_ipv4_skips_dotted_numbers_and_versions() {
+    let options = PiiOptions::only(&[PiiKind::Ipv4]);
+    for text in [
+      "oid 1.3.6.1.4.1 and 10.2.3.4.5",
+      "version = \"1.2.3.4\"",
+      "  \"Version\": \"4.1.0.2\",",
+      "requests==2.31.0.1",
+      "build_10.2.3.4",
+    ] {
+      assert!(detect_pii(text, &options).is_empty(), "{}", text);
+    }
+    let found = kinds("DB_HOST=10.2.3.4\nrelease 1.2\n", &options);
+    assert_eq!(found, vec![(PiiKind::Ipv4, "10.2.3.4".to_string())]);
+  }
+
+  #[test]
+  fn test_per_type_toggles_and_domain_allowlist() {
+    let mut options = PiiOptions::only(&[PiiKind::Email]);
+    options.allowed_email_domains.push("corp.io".to_string());
+    let found = kinds("ops@corp.io x@vendor.com 10.1.1.1", &options);
+    assert_eq!(found, vec![(PiiKind::Email, "x@vendor.com".to_string())]);
+  }
+}
@@ -0,0 +1,687 @@
+//! Prose-aware chunking for Markdown, reStructuredText and AsciiDoc: chunks follow the heading
+//! hierarchy and paragraphs, long paragraphs are split at sentences, and code blocks stay whole
+//! unless they exceed the budget on their own.
+use crate::source_map::{LineIndex, SourceSpan};
+use crate::text_chunker::{chunk_text, ChunkerConfig, Lines, TextChunk};
+use std::ops::Range;
+use std::path::Path;
+
+/// Document formats understood by the prose chunker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProseFormat {
+  Markdown,
+  ReStructuredText,
+  AsciiDoc,
+}
+
+impl ProseFormat {
+  /// Detects the format from the file extension.
+  pub fn from_path(file_path: &str) -> Option<Self> {
+    let ext = Path::new(file_path)
+      .extension()?
+      .to_str()?
+      .to_ascii_lowercase();
+    match ext.as_str() {
+      "md" | "markdown" | "mdx" => Some(Self::Markdown),
+      "rst" => Some(Self::ReStructuredText),
+      "adoc" | "asciidoc" => Some(Self::AsciiDoc),
+      _ => None,
+    }
+  }
+
+  /// Human-readable name of the format.
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Markdown => "Markdown",
+      Self::ReStructuredText => "reStructuredText",
+      Self::AsciiDoc => "AsciiDoc",
+    }
+  }
+
+  fn blocks(self, lines: &[&str]) -> Vec<Block> {
+    match self {
+      Self::Markdown => markdown_blocks(lines),
+      Self::ReStructuredText => rst_blocks(lines),
+      Self::AsciiDoc => asciidoc_blocks(lines),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+enum BlockKind {
+  Heading { level: usize, title: String },
+  Text,
+  Code,
+}
+
+/// A structural block of a document, as a range of lines.
+#[derive(Debug)]
+struct Block {
+  kind: BlockKind,
+  lines: Range<usize>,
+}
+
+/// Collects blocks, grouping runs of ordinary lines into paragraphs.
+#[derive(Default)]
+struct BlockBuilder {
+  blocks: Vec<Block>,
+  paragraph: Option<usize>,
+}
+
+impl BlockBuilder {
+  fn line(&mut self, index: usize) {
+    self.paragraph.get_or_insert(index);
+  }
+
+  fn close(&mut self, end: usize) {
+    if let Some(start) = self.paragraph.take() {
+      self.blocks.push(Block {
+        kind: BlockKind::Text,
+        lines: start..end,
+      });
+    }
+  }
+
+  fn push(&mut self, kind: BlockKind, lines: Range<usize>) {
+    self.close(lines.start);
+    self.blocks.push(Block { kind, lines });
+  }
+
+  fn finish(mut self, end: usize) -> Vec<Block> {
+    self.close(end);
+    self.blocks
+  }
+}
+
+fn indent(line: &str) -> usize {
+  line.len() - line.trim_start().len()
+}
+
+/// The fence character and length opening a Markdown code fence.
+fn markdown_fence(trimmed: &str) -> Option<(char, usize)> {
+  let fence = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+  let len = trimmed.chars().take_while(|c| *c == fence).count();
+  (len >= 3).then_some((fence, len))
+}
+
+fn atx_heading(trimmed: &str) -> Option<(usize, String)> {
+  let level = trimmed.chars().take_while(|c| *c == '#').count();
+  let rest = &trimmed[level..];
+  if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+    return None;
+  }
+  Some((
+    level,
+    rest.trim().trim_end_matches('#').trim_end().to_string(),
+  ))
+}
+
+fn markdown_blocks(lines: &[&str]) -> Vec<Block> {
+  let mut blocks = BlockBuilder::default();
+  let mut i = 0;
+  while i < lines.len() {
+    let trimmed = lines[i].trim();
+    if trimmed.is_empty() {
+      blocks.close(i);
+      i += 1;
+      continue;
+    }
+    if indent(lines[i]) < 4 {
+      if let Some((fence, len)) = markdown_fence(trimmed) {
+        let end = (i + 1..lines.len())
+          .find(|&j| {
+            let close = lines[j].trim();
+            close.len() >= len && close.chars().all(|c| c == fence)
+          })
+          .map_or(lines.len(), |j| j + 1);
+        blocks.push(BlockKind::Code, i..end);
+        i = end;
+        continue;
+      }
+      if let Some((level, title)) = atx_heading(trimmed) {
+        blocks.push(BlockKind::Heading { level, title }, i..i + 1);
+        i += 1;
+        continue;
+      }
+      // Setext headings underline the paragraph above them
+      let setext = if trimmed.chars().all(|c| c == '=') {
+        Some(1)
+      } else if trimmed.chars().all(|c| c == '-') {
+        Some(2)
+      } else {
+        None
+      };
+      if let (Some(level), Some(start)) = (setext, blocks.paragraph) {
+        blocks.paragraph = None;
+        let title = lines[start..i]
+          .iter()
+          .map(|line| line.trim())
+          .collect::<Vec<_>>()
+          .join(" ");
+        blocks.push(BlockKind::Heading { level, title }, start..i + 1);
+        i += 1;
+        continue;
+      }
+    }
+    blocks.line(i);
+    i += 1;
+  }
+  blocks.finish(lines.len())
+}
+
+/// The character of a reStructuredText section adornment line such as `=====`.
+fn rst_adornment(line: &str) -> Option<char> {
+  let first = line.chars().next().filter(|c| c.is_ascii_punctuation())?;
+  line.trim_end().chars().all(|c| c == first).then_some(first)
+}
+
+/// End of the indented block starting at `start`: lines indented deeper than `base`, and the
+/// blank lines between them.
+fn indented_end(lines: &[&str], start: usize, base: usize) -> usize {
+  let mut end = start;
+  for (j, line) in lines.iter().enumerate().skip(start) {
+    if line.trim().is_empty() {
+      continue;
+    }
+    if indent(line) <= base {
+      break;
+    }
+    end = j + 1;
+  }
+  end
+}
+
+fn rst_blocks(lines: &[&str]) -> Vec<Block> {
+  let mut blocks = BlockBuilder::default();
+  // Heading levels follow the order in which adornment styles first appear
+  let mut styles: Vec<(char, bool)> = Vec::new();
+  let mut level_of = |style: (char, bool)| match styles.iter().position(|s| *s == style) {
+    Some(position) => position + 1,
+    None => {
+      styles.push(style);
+      styles.len()
+    }
+  };
+
+  let mut i = 0;
+  while i < lines.len() {
+    let line = lines[i];
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+      // A paragraph ending in `::` introduces an indented literal block
+      let literal = blocks
+        .paragraph
+        .filter(|_| lines[i - 1].trim_end().ends_with("::"));
+      blocks.close(i);
+      if let Some(start) = literal {
+        let end = indented_end(lines, i, indent(lines[start]));
+        if end > i {
+          let first = (i..end).find(|&j| !lines[j].trim().is_empty()).unwrap_or(i);
+          blocks.push(BlockKind::Code, first..end);
+          i = end;
+          continue;
+        }
+      }
+      i += 1;
+      continue;
+    }
+    if [".. code-block::", ".. code::", ".. sourcecode::"]
+      .iter()
+      .any(|d| trimmed.starts_with(d))
+    {
+      let end = indented_end(lines, i + 1, indent(line)).max(i + 1);
+      blocks.push(BlockKind::Code, i..end);
+      i = end;
+      continue;
+    }
+    if let Some(c) = rst_adornment(line) {
+      let overlined = i + 2 < lines.len()
+        && !lines[i + 1].trim().is_empty()
+        && lines[i + 2].trim_end() == line.trim_end();
+      if overlined {
+        let level = level_of((c, true));
+        blocks.push(
+          BlockKind::Heading {
+            level,
+            title: lines[i + 1].trim().to_string(),
+          },
+          i..i + 3,
+        );
+        i += 3;
+        continue;
+      }
+    }
+    if blocks.paragraph.is_none() && rst_adornment(line).is_none() && i + 1 < lines.len() {
+      let underline = lines[i + 1].trim_end();
+      if let Some(c) = rst_adornment(underline) {
+        if underline.chars().count() >= trimmed.chars().count() {
+          let level = level_of((c, false));
+          blocks.push(
+            BlockKind::Heading {
+              level,
+              title: trimmed.to_string(),
+            },
+            i..i + 2,
+          );
+          i += 2;
+          continue;
+        }
+      }
+    }
+    blocks.line(i);
+    i += 1;
+  }
+  blocks.finish(lines.len())
+}
+
+/// The line closing an AsciiDoc delimited block (listing, literal, passthrough, comment or
+/// fenced) opened by `line`.
+fn asciidoc_delimiter(line: &str) -> Option<&str> {
+  if line.starts_with("```") {
+    return Some("```");
+  }
+  let first = line
+    .chars()
+    .next()
+    .filter(|c| matches!(c, '-' | '.' | '+' | '/'))?;
+  (line.len() >= 4 && line.chars().all(|c| c == first)).then_some(line)
+}
+
+fn asciidoc_blocks(lines: &[&str]) -> Vec<Block> {
+  let mut blocks = BlockBuilder::default();
+  let mut i = 0;
+  while i < lines.len() {
+    let line = lines[i].trim_end();
+    if line.trim().is_empty() {
+      blocks.close(i);
+      i += 1;
+      continue;
+    }
+    if let Some(delimiter) = asciidoc_delimiter(line) {
+      // An attribute line such as `[source,rust]` directly above belongs to the block
+      let start = match blocks.paragraph {
+        Some(start)
+          if start + 1 == i
+            && lines[start].starts_with('[')
+            && lines[start].trim_end().ends_with(']') =>
+        {
+          blocks.paragraph = None;
+          start
+        }
+        _ => i,
+      };
+      let end = (i + 1..lines.len())
+        .find(|&j| lines[j].trim_end() == delimiter)
+        .map_or(lines.len(), |j| j + 1);
+      blocks.push(BlockKind::Code, start..end);
+      i = end;
+      continue;
+    }
+    let level = line.chars().take_while(|c| *c == '=').count();
+    if (1..=6).contains(&level)
+      && line[level..].starts_with(' ')
+      && !line[level..].trim().is_empty()
+    {
+      blocks.push(
+        BlockKind::Heading {
+          level,
+          title: line[level..].trim().to_string(),
+        },
+        i..i + 1,
+      );
+      i += 1;
+      continue;
+    }
+    blocks.line(i);
+    i += 1;
+  }
+  blocks.finish(lines.len())
+}
+
+/// Byte ranges of the sentences of `text`, each including the whitespace after it.
+fn sentence_spans(text: &str) -> Vec<Range<usize>> {
+  let mut spans = Vec::new();
+  let mut start = 0;
+  let mut chars = text.char_indices().peekable();
+  while let Some((_, c)) = chars.next() {
+    if matches!(c, '.' | '!' | '?') && chars.peek().is_some_and(|(_, next)| next.is_whitespace()) {
+      while chars.peek().is_some_and(|(_, next)| next.is_whitespace()) {
+        chars.next();
+      }
+      let end = chars.peek().map_or(text.len(), |(j, _)| *j);
+      spans.push(start..end);
+      start = end;
+    }
+  }
+  if start < text.len() {
+    spans.push(start..text.len());
+  }
+  spans
+}
+
+/// Packs blocks into chunks, tracking the heading path they belong to.
+struct Packer<'a> {
+  lines: &'a Lines<'a>,
+  index: &'a LineIndex<'a>,
+  config: &'a ChunkerConfig,
+  chunks: Vec<TextChunk>,
+  current: Option<Range<usize>>,
+  has_body: bool,
+  heading_path: Option<String>,
+}
+
+impl Packer<'_> {
+  /// Adds a block to the current chunk if it fits, or starts a new chunk with it. Headings always
+  /// stay with the block that follows them.
+  fn add(&mut self, range: Range<usize>, body: bool) {
+    if let Some(current) = &mut self.current {
+      if !self.has_body || self.lines.tokens(&(current.start..range.end)) <= self.config.chunk_size
+      {
+        current.end = range.end;
+        self.has_body |= body;
+        return;
+      }
+    }
+    self.flush();
+    self.current = Some(range);
+    self.has_body = body;
+  }
+
+  fn flush(&mut self) {
+    if let Some(range) = self.current.take() {
+      self.push(
+        self.lines.text(&range),
+        self.index.lines_span(range.start, range.end),
+        None,
+      );
+    }
+    self.has_body = false;
+  }
+
+  fn push(&mut self, content: String, span: SourceSpan, parent: Option<String>) {
+    if content.trim().is_empty() {
+      return;
+    }
+    self.chunks.push(TextChunk {
+      content,
+      start_index: span.start.line - 1,
+      end_index: span.end.line,
+      chunk_index: self.chunks.len(),
+      span,
+      heading_path: self.heading_path.clone(),
+      parent,
+    });
+  }
+
+  /// Lines of headings waiting for body text, to lead the first piece of a split block starting
+  /// at `start`. Anything else in the current chunk is flushed.
+  fn take_lead(&mut self, start: usize) -> Option<Range<usize>> {
+    match self.current.clone() {
+      Some(current) if !self.has_body => {
+        self.current = None;
+        Some(current.start..start)
+      }
+      _ => {
+        self.flush();
+        None
+      }
+    }
+  }
+
+  /// Splits a code block over budget at line boundaries. Every piece is marked as a part of the
+  /// block, so pieces after the first read as its continuation.
+  fn add_code_lines(&mut self, range: Range<usize>) {
+    let lead = self.take_lead(range.start);
+    let mut pieces: Vec<Range<usize>> = Vec::new();
+    let mut start = range.start;
+    let mut tokens = 0;
+    for line in range.clone() {
+      let line_tokens = self.lines.tokens(&(line..line + 1));
+      if line > start && tokens + line_tokens > self.config.chunk_size {
+        pieces.push(start..line);
+        start = line;
+        tokens = 0;
+      }
+      tokens += line_tokens;
+    }
+    pieces.push(start..range.end);
+
+    let total = pieces.len();
+    for (i, mut piece) in pieces.into_iter().enumerate() {
+      if let (0, Some(lead)) = (i, &lead) {
+        piece.start = lead.start;
+      }
+      let part = format!("code block, part {} of {}", i + 1, total);
+      self.push(
+        self.lines.text(&piece),
+        self.index.lines_span(piece.start, piece.end),
+        Some(part),
+      );
+    }
+  }
+
+  /// Splits an oversized paragraph at sentence boundaries, falling back to the line chunker for
+  /// single sentences over budget. Headings waiting for body text lead the first piece.
+  fn add_sentences(&mut self, range: Range<usize>) {
+    let mut lead = self.take_lead(range.start);
+    let text = self.lines.lines[range.clone()].join("\n");
+    let paragraph = LineIndex::new(&text);
+    // Spans of byte ranges of the paragraph, in the document
+    let span_of = |bytes: Range<usize>| {
+      let span = SourceSpan {
+        start: paragraph.position_at(bytes.start),
+        end: paragraph.position_at(bytes.end),
+      };
+      self.index.rebase(&span, &paragraph, (range.start, 0))
+    };
+
+    let mut pieces: Vec<(String, SourceSpan)> = Vec::new();
+    let mut group: Option<Range<usize>> = None;
+    for sentence in sentence_spans(&text) {
+      if let Some(current) = &mut group {
+        if self.config.count_tokens(&text[current.start..sentence.end]) <= self.config.chunk_size {
+          current.end = sentence.end;
+          continue;
+        }
+        let piece = text[current.clone()].trim_end();
+        pieces.push((
+          format!("{}\n", piece),
+          span_of(current.start..current.start + piece.len()),
+        ));
+        group = None;
+      }
+      if self.config.count_tokens(&text[sentence.clone()]) > self.config.chunk_size {
+        let sentence_index = LineIndex::new(&text[sentence.clone()]);
+        let origin = paragraph.locate(&paragraph.position_at(sentence.start));
+        for piece in chunk_text(&text[sentence.clone()], self.config) {
+          let span = paragraph.rebase(&piece.span, &sentence_index, origin);
+          pieces.push((
+            piece.content,
+            self.index.rebase(&span, &paragraph, (range.start, 0)),
+          ));
+        }
+      } else {
+        group = Some(sentence);
+      }
+    }
+    if let Some(current) = group {
+      let piece = text[current.clone()].trim_end();
+      pieces.push((
+        format!("{}\n", piece),
+        span_of(current.start..current.start + piece.len()),
+      ));
+    }
+
+    for (content, span) in pieces {
+      match lead.take() {
+        Some(lead) => {
+          let span = SourceSpan {
+            start: self.index.position(lead.start, 0),
+            end: span.end,
+          };
+          self.push(format!("{}{}", self.lines.text(&lead), content), span, None);
+        }
+        None => self.push(content, span, None),
+      }
+    }
+  }
+}
+
+/// Chunks a Markdown, reStructuredText or AsciiDoc document along its headings. A heading starts
+/// a new chunk unless only headings precede it in the current one; blocks are packed up to the
+/// budget, paragraphs over it are split at sentences and code blocks over it at lines. Returns
+/// `None` if `file_path` is not a prose format.
+pub(crate) fn chunk_prose(
+  text: &str,
+  file_path: &str,
+  config: &ChunkerConfig,
+) -> Option<Vec<TextChunk>> {
+  let format = ProseFormat::from_path(file_path)?;
+  let lines = Lines::new(text, config);
+  let index = LineIndex::new(text);
+  let blocks = format.blocks(&lines.lines);
+
+  let mut headings: Vec<(usize, String)> = Vec::new();
+  let mut packer = Packer {
+    lines: &lines,
+    index: &index,
+    config,
+    chunks: Vec::new(),
+    current: None,
+    has_body: false,
+    heading_path: None,
+  };
+  for block in blocks {
+    match block.kind {
+      BlockKind::Heading { level, title } => {
+        if packer.has_body {
+          packer.flush();
+        }
+        headings.retain(|(parent, _)| *parent < level);
+        headings.push((level, title));
+        let titles: Vec<&str> = headings.iter().map(|(_, title)| title.as_str()).collect();
+        packer.heading_path = Some(titles.join(" > "));
+        packer.add(block.lines, false);
+      }
+      BlockKind::Text if lines.tokens(&block.lines) > config.chunk_size => {
+        packer.add_sentences(block.lines)
+      }
+      BlockKind::Code if lines.tokens(&block.lines) > config.chunk_size => {
+        packer.add_code_lines(block.lines)
+      }
+      BlockKind::Text | BlockKind::Code => packer.add(block.lines, true),
+    }
+  }
+  packer.flush();
+  Some(packer.chunks)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::text_chunker::ChunkingStrategy;
+
+  fn config(chunk_size: usize) -> ChunkerConfig {
+    ChunkerConfig {
+      chunk_size,
+      overlap_size: 0,
+      strategy: ChunkingStrategy::Syntax,
+      tokenizer: None,
+    }
+  }
+
+  fn paths(chunks: &[TextChunk]) -> Vec<Option<&str>> {
+    chunks
+      .iter()
+      .map(|chunk| chunk.heading_path.as_deref())
+      .collect()
+  }
+
+  #[test]
+  fn test_markdown_sections_and_fences() {
+    let doc = "Intro text.\n\n# Install\n\n## Linux\n\nUse the package.\n\n```sh\n# not a heading\n\napt install toak\n```\n\n### Packages\n\nDeb and RPM.\n\n## macOS\n\nUse Homebrew.\n";
+    let chunks = chunk_prose(doc, "docs/guide.md", &config(200)).unwrap();
+    assert_eq!(
+      paths(&chunks),
+      vec![
+        None,
+        Some("Install > Linux"),
+        Some("Install > Linux > Packages"),
+        Some("Install > macOS")
+      ]
+    );
+    assert!(chunks[1].content.starts_with("# Install\n\n## Linux"));
+    assert!(chunks[1]
+      .content
+      .contains("# not a heading\n\napt install toak\n```"));
+    assert_eq!((chunks[3].start_index, chunks[3].end_index), (18, 21));
+    assert_eq!(
+      &doc[chunks[3].span.start.byte..chunks[3].span.end.byte],
+      "## macOS\n\nUse Homebrew."
+    );
+  }
+
+  #[test]
+  fn test_long_paragraphs_split_at_sentences() {
+    let sentence = "This sentence has exactly a handful of words in it.";
+    let paragraph = [sentence; 12].join(" ");
+    let doc = format!("Usage\n=====\n\n{}\n", paragraph);
+    let chunks = chunk_prose(&doc, "README.markdown", &config(40)).unwrap();
+    assert!(chunks.len() > 1);
+    assert!(chunks[0]
+      .content
+      .starts_with("Usage\n=====\n\nThis sentence"));
+    assert!(chunks
+      .iter()
+      .all(|c| c.content.trim_end().ends_with("in it.")));
+    assert!(chunks
+      .iter()
+      .all(|c| c.heading_path.as_deref() == Some("Usage")));
+    assert_eq!(chunks[1].span.start.line, 4);
+    assert_eq!(
+      &doc[chunks[1].span.start.byte..chunks[1].span.end.byte],
+      chunks[1].content.trim_end()
+    );
+
+    // Code blocks over budget are split at lines, each piece marked as a part of the block
+    let fence = format!("# Code\n\n```\n{}```\n", "let x = 1;\n".repeat(30));
+    let chunks = chunk_prose(&fence, "a.md", &config(20)).unwrap();
+    assert!(chunks.len() > 1);
+    assert!(chunks[0].content.starts_with("# Code\n\n```\nlet x = 1;"));
+    assert!(chunks[1..]
+      .iter()
+      .all(|c| c.content.starts_with("let x = 1;") && c.content.lines().count() <= 5));
+    assert_eq!(
+      chunks[1].parent,
+      Some(format!("code block, part 2 of {}", chunks.len()))
+    );
+    assert!(chunks.last().unwrap().content.ends_with("```\n"));
+    let rejoined: String = chunks.iter().map(|c| c.content.as_str()).collect();
+    assert_eq!(rejoined, fence);
+  }
+
+  #[test]
+  fn test_rst_and_asciidoc_headings() {
+    let rst = "=====\nGuide\n=====\n\nInstall\n-------\n\nRun this::\n\n    toak generate\n\n    toak search\n\nLinux\n~~~~~\n\nText.\n\nUsage\n-----\n\n.. code-block:: bash\n\n   toak\n";
+    let chunks = chunk_prose(rst, "guide.rst", &config(200)).unwrap();
+    assert_eq!(
+      paths(&chunks),
+      vec![
+        Some("Guide > Install"),
+        Some("Guide > Install > Linux"),
+        Some("Guide > Usage")
+      ]
+    );
+    assert!(chunks[0].content.ends_with("    toak search\n"));
+
+    let adoc = "= Guide\n\n== Install\n\n[source,bash]\n----\n== not a heading\n----\n\n=== Linux\n\nText.\n";
+    let chunks = chunk_prose(adoc, "guide.adoc", &config(200)).unwrap();
+    assert_eq!(
+      paths(&chunks),
+      vec![Some("Guide > Install"), Some("Guide > Install > Linux")]
+    );
+    assert!(chunks[0]
+      .content
+      .contains("[source,bash]\n----\n== not a heading\n----\n"));
+  }
+}
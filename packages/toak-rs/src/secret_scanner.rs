@@ -0,0 +1,326 @@
+//! Runs the secret detectors over a repository without generating any artifacts, so the
+//! redaction engine can double as a CI gate in front of LLM context generation.
+use crate::markdown_generator::{MarkdownGenerator, MarkdownGeneratorOptions};
+use crate::token_cleaner::{
+  scan_secrets, secret_rule_description, FingerprintKey, RedactionFinding, RedactionOptions,
+  SecretAllowlist, Severity,
+};
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Output format for scan results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanFormat {
+  #[default]
+  Human,
+  Json,
+  Sarif,
+}
+
+impl std::str::FromStr for ScanFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "human" | "text" => Ok(Self::Human),
+      "json" => Ok(Self::Json),
+      "sarif" => Ok(Self::Sarif),
+      other => Err(format!(
+        "unknown scan format '{}' (expected human, json or sarif)",
+        other
+      )),
+    }
+  }
+}
+
+/// Configuration for a secret scan. File selection mirrors markdown generation.
+pub struct ScanOptions {
+  pub dir: PathBuf,
+  pub file_type_exclusions: HashSet<String>,
+  pub file_exclusions: Vec<String>,
+  pub verbose: bool,
+  /// Known false positives to suppress, on top of the project's allowlist and baseline
+  pub allowlist: SecretAllowlist,
+  /// Key of the finding fingerprints; `None` uses the project's key
+  pub fingerprint_key: Option<FingerprintKey>,
+  /// Report findings the project's baseline would hide, e.g. to write a new baseline
+  pub ignore_baseline: bool,
+}
+
+impl Default for ScanOptions {
+  fn default() -> Self {
+    let defaults = MarkdownGeneratorOptions::default();
+    Self {
+      dir: defaults.dir,
+      file_type_exclusions: defaults.file_type_exclusions,
+      file_exclusions: defaults.file_exclusions,
+      verbose: false,
+      allowlist: SecretAllowlist::default(),
+      fingerprint_key: None,
+      ignore_baseline: false,
+    }
+  }
+}
+
+/// Findings produced by a scan run.
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+  pub files_scanned: usize,
+  pub findings: Vec<RedactionFinding>,
+  /// Number of findings dropped by inline `toak:allow` markers or the allowlist
+  pub suppressed: usize,
+}
+
+impl ScanResult {
+  /// Whether any finding is at or above `threshold`.
+  pub fn exceeds(&self, threshold: Severity) -> bool {
+    self.findings.iter().any(|f| f.severity >= threshold)
+  }
+
+  /// Renders the result in the requested format.
+  pub fn render(&self, format: ScanFormat) -> Result<String> {
+    match format {
+      ScanFormat::Human => Ok(self.render_human()),
+      ScanFormat::Json => Ok(serde_json::to_string_pretty(&json!({
+        "files_scanned": self.files_scanned,
+        "suppressed": self.suppressed,
+        "total_findings": self.findings.len(),
+        "findings": self.findings,
+      }))?),
+      ScanFormat::Sarif => Ok(serde_json::to_string_pretty(&self.to_sarif())?),
+    }
+  }
+
+  fn render_human(&self) -> String {
+    let mut out = String::new();
+    for finding in &self.findings {
+      out.push_str(&format!(
+        "{}:{}:{}: [{}] {} (fingerprint {})\n",
+        finding.file_path,
+        finding.line,
+        finding.column,
+        finding.severity,
+        finding.rule_id,
+        finding.fingerprint
+      ));
+    }
+    out.push_str(&format!(
+      "Scanned {} files: {} findings, {} suppressed\n",
+      self.files_scanned,
+      self.findings.len(),
+      self.suppressed
+    ));
+    out
+  }
+
+  /// Builds a SARIF 2.1.0 log for code scanning integrations.
+  fn to_sarif(&self) -> serde_json::Value {
+    let rule_ids: BTreeMap<&str, Severity> = self
+      .findings
+      .iter()
+      .map(|f| (f.rule_id.as_str(), f.severity))
+      .collect();
+    let rules: Vec<serde_json::Value> = rule_ids
+      .iter()
+      .map(|(id, severity)| {
+        json!({
+          "id": id,
+          "shortDescription": { "text": secret_rule_description(id).unwrap_or(id) },
+          "defaultConfiguration": { "level": sarif_level(*severity) },
+        })
+      })
+      .collect();
+
+    let results: Vec<serde_json::Value> = self
+      .findings
+      .iter()
+      .map(|f| {
+        json!({
+          "ruleId": f.rule_id,
+          "level": sarif_level(f.severity),
+          "message": { "text": format!("Possible secret detected by rule '{}'", f.rule_id) },
+          "locations": [{
+            "physicalLocation": {
+              "artifactLocation": { "uri": f.file_path },
+              "region": { "startLine": f.line, "startColumn": f.column },
+            }
+          }],
+          "partialFingerprints": { "secretFingerprint/v1": f.fingerprint },
+        })
+      })
+      .collect();
+
+    json!({
+      "version": "2.1.0",
+      "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+      "runs": [{
+        "tool": {
+          "driver": {
+            "name": "toak",
+            "version": env!("CARGO_PKG_VERSION"),
+            "informationUri": "https://github.com/geoffsee/toak",
+            "rules": rules,
+          }
+        },
+        "results": results,
+      }]
+    })
+  }
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+  match severity {
+    Severity::High => "error",
+    Severity::Medium => "warning",
+    Severity::Low => "note",
+  }
+}
+
+/// Scans file contents for secrets and suspicious Unicode, dropping findings suppressed inline
+/// or by `allowlist`. Returns the kept findings, fingerprinted under `key`, and the number
+/// suppressed.
+pub fn scan_content(
+  content: &str,
+  file_path: &str,
+  allowlist: &SecretAllowlist,
+  key: &FingerprintKey,
+) -> (Vec<RedactionFinding>, usize) {
+  let options = RedactionOptions {
+    allowlist: allowlist.clone(),
+    fingerprint_key: Some(key.clone()),
+    ..Default::default()
+  };
+  scan_secrets(content, file_path, &options)
+}
+
+/// Runs the secret detectors over the selected files of a repository.
+pub struct SecretScanner {
+  options: ScanOptions,
+}
+
+impl SecretScanner {
+  pub fn new(options: ScanOptions) -> Self {
+    Self { options }
+  }
+
+  /// Scans every selected file and collects findings.
+  pub async fn scan(&self) -> Result<ScanResult> {
+    let mut selector = MarkdownGenerator::new(MarkdownGeneratorOptions {
+      dir: self.options.dir.clone(),
+      file_type_exclusions: self.options.file_type_exclusions.clone(),
+      file_exclusions: self.options.file_exclusions.clone(),
+      verbose: self.options.verbose,
+      ..Default::default()
+    });
+    let files = selector
+      .get_tracked_files()
+      .await
+      .map_err(|e| anyhow!("Failed to list files to scan: {}", e))?;
+
+    let mut result = ScanResult {
+      files_scanned: 0,
+      findings: Vec::new(),
+      suppressed: 0,
+    };
+
+    let dir = &self.options.dir;
+    let mut allowlist = SecretAllowlist::load(dir)?;
+    if self.options.ignore_baseline {
+      allowlist.fingerprints.clear();
+    }
+    allowlist.extend(self.options.allowlist.clone());
+    let key = match &self.options.fingerprint_key {
+      Some(key) => key.clone(),
+      None => FingerprintKey::load_or_default(dir)?,
+    };
+
+    for file in files {
+      let content = match fs::read_to_string(self.options.dir.join(&file)).await {
+        Ok(content) => content,
+        Err(e) => {
+          // Binary or unreadable files cannot contain text secrets we would emit
+          if self.options.verbose {
+            eprintln!("Skipping {}: {}", file, e);
+          }
+          continue;
+        }
+      };
+
+      let (findings, suppressed) = scan_content(&content, &file, &allowlist, &key);
+      result.files_scanned += 1;
+      result.suppressed += suppressed;
+      result.findings.extend(findings);
+    }
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_scan_content_counts_suppressed() {
+    let content = "\
+const API_KEY = \"live-123\"; // toak:allow
+// toak:allow test fixture
+const AUTH_TOKEN = \"fixture-456\";
+const CLIENT_SECRET = \"real-789\";
+";
+    let (findings, suppressed) = scan_content(
+      content,
+      "config.js",
+      &SecretAllowlist::default(),
+      &FingerprintKey::default(),
+    );
+    assert_eq!(suppressed, 2);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].line, 4);
+  }
+
+  #[test]
+  fn test_scan_content_sees_through_invisible_characters() {
+    let (findings, _) = scan_content(
+      "api_\u{200B}key = \"sk-live-1\"",
+      "config.py",
+      &SecretAllowlist::default(),
+      &FingerprintKey::default(),
+    );
+    let found: Vec<_> = findings
+      .iter()
+      .map(|f| (f.rule_id.as_str(), f.column))
+      .collect();
+    assert_eq!(found, [("unicode-invisible", 5), ("assigned-secret", 13)]);
+  }
+
+  #[test]
+  fn test_threshold_and_sarif_rendering() {
+    let (findings, _) = scan_content(
+      "digest = \"da39a3ee5e6b4b0d3255bfef95601890afd80709\"",
+      "a.txt",
+      &SecretAllowlist::default(),
+      &FingerprintKey::default(),
+    );
+    let result = ScanResult {
+      files_scanned: 1,
+      findings,
+      suppressed: 0,
+    };
+    assert!(result.exceeds(Severity::Low));
+    assert!(!result.exceeds(Severity::Medium));
+
+    let sarif: serde_json::Value =
+      serde_json::from_str(&result.render(ScanFormat::Sarif).unwrap()).unwrap();
+    let sarif_result = &sarif["runs"][0]["results"][0];
+    assert_eq!(sarif_result["ruleId"], "hex-40");
+    assert_eq!(sarif_result["level"], "note");
+    assert_eq!(
+      sarif_result["locations"][0]["physicalLocation"]["region"]["startLine"],
+      1
+    );
+  }
+}
@@ -3,18 +3,33 @@
 //! This module provides tools for performing semantic similarity searches
 //! against embeddings stored in JSON format.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-use crate::embeddings_generator::EmbeddingsGenerator;
+use crate::embedding_backend::{EmbeddingBackend, EmbeddingBackendConfig};
+use crate::embeddings_generator::is_same_model;
+use crate::json_database_generator::{is_unit_length, ChunkLocation, ChunkMetadata};
+use crate::source_map::SourceSpan;
 
 /// Represents a chunk with its embedding from the embeddings database
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingChunk {
     pub file_path: String,
     pub content: String,
+    /// SHA-256 of the content; empty in databases written by older versions
+    #[serde(default)]
+    pub content_hash: String,
+    /// Context header embedded ahead of the content, if the database was generated with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
     pub embedding: Vec<f32>,
+    /// Chunk metadata; absent in databases written by older versions
+    #[serde(default)]
+    pub metadata: Option<ChunkMetadata>,
+    /// Other locations with the same content
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<ChunkLocation>,
 }
 
 /// Metadata about the embeddings database
@@ -23,10 +38,18 @@ pub struct EmbeddingsDatabaseMetadata {
     pub version: String,
     pub generated_at: String,
     pub model: String,
+    pub dimension: Option<usize>,
+    pub normalized: Option<bool>,
     pub chunk_size: usize,
     pub overlap_size: usize,
     pub total_files: usize,
+    /// Unique chunks, each with its own embedding
     pub total_chunks: usize,
+    /// Places chunk content occurs, counting the duplicates of unique chunks
+    pub total_locations: usize,
+    /// Whether the last query's embedding had unit length when the database's did not, or the
+    /// other way round, which hints that they come from different models
+    pub normalization_mismatch: bool,
 }
 
 /// The complete embeddings database structure
@@ -35,6 +58,12 @@ pub struct EmbeddingsDatabase {
     pub version: String,
     pub generated_at: String,
     pub model: String,
+    /// Length of the embedding vectors; absent in databases written by older versions
+    #[serde(default)]
+    pub dimension: Option<usize>,
+    /// Whether the embedding vectors have unit length
+    #[serde(default)]
+    pub normalized: Option<bool>,
     pub chunk_size: usize,
     pub overlap_size: usize,
     pub total_files: usize,
@@ -48,42 +77,89 @@ pub struct SearchResult {
     pub file_path: String,
     pub content: String,
     pub similarity: f32,
+    /// Section headings the chunk came from, for documents
+    pub heading_path: Option<String>,
+    /// Where the chunk came from in the original file
+    pub span: Option<SourceSpan>,
+    /// Other files and places the same content occurs
+    pub duplicates: Vec<ChunkLocation>,
 }
 
 /// Semantic search engine for querying embeddings databases
 pub struct SemanticSearch {
     database: EmbeddingsDatabase,
-    generator: EmbeddingsGenerator,
+    backend: Box<dyn EmbeddingBackend>,
+    normalization_mismatch: bool,
+}
+
+fn load_database(embeddings_path: &Path) -> Result<EmbeddingsDatabase> {
+    let contents = std::fs::read_to_string(embeddings_path)
+        .context("Failed to read embeddings file")?;
+
+    serde_json::from_str(&contents)
+        .context("Failed to parse embeddings JSON")
 }
 
 impl SemanticSearch {
-    /// Create a new semantic search instance by loading an embeddings database
+    /// Create a new semantic search instance by loading an embeddings database, embedding
+    /// queries with the local model the database was generated with
     pub fn new<P: AsRef<Path>>(embeddings_path: P) -> Result<Self> {
-        let contents = std::fs::read_to_string(embeddings_path.as_ref())
-            .context("Failed to read embeddings file")?;
-
-        let database: EmbeddingsDatabase = serde_json::from_str(&contents)
-            .context("Failed to parse embeddings JSON")?;
+        let database = load_database(embeddings_path.as_ref())?;
+        let Some(backend) = EmbeddingBackendConfig::for_model(&database.model) else {
+            bail!(
+                "the database was embedded with {}, which is not a local model; search it through the backend that generated it",
+                database.model
+            );
+        };
+        let backend = backend.create()
+            .context("Failed to initialize embeddings generator")?;
+        Self::from_parts(database, backend)
+    }
 
-        let generator = EmbeddingsGenerator::new()
+    /// Load an embeddings database, embedding queries with the configured backend
+    pub fn with_backend_config<P: AsRef<Path>>(embeddings_path: P, backend: &EmbeddingBackendConfig) -> Result<Self> {
+        let database = load_database(embeddings_path.as_ref())?;
+        // Fail before loading a model that cannot be used
+        check_model(&database, &backend.model_id()?)?;
+        let backend = backend.create()
             .context("Failed to initialize embeddings generator")?;
+        Self::from_parts(database, backend)
+    }
 
+    /// Load an embeddings database, embedding queries with `backend`. Fails unless the backend's
+    /// model is the one that embedded the database.
+    pub fn with_backend<P: AsRef<Path>>(embeddings_path: P, backend: Box<dyn EmbeddingBackend>) -> Result<Self> {
+        Self::from_parts(load_database(embeddings_path.as_ref())?, backend)
+    }
+
+    fn from_parts(database: EmbeddingsDatabase, backend: Box<dyn EmbeddingBackend>) -> Result<Self> {
+        check_model(&database, &backend.model_id())?;
         Ok(Self {
             database,
-            generator,
+            backend,
+            normalization_mismatch: false,
         })
     }
 
+    /// Length of the database's vectors, from its metadata or, for older databases, its chunks
+    fn dimension(&self) -> Option<usize> {
+        self.database.dimension.or_else(|| self.database.chunks.first().map(|chunk| chunk.embedding.len()))
+    }
+
     /// Get metadata about the loaded database
     pub fn metadata(&self) -> EmbeddingsDatabaseMetadata {
         EmbeddingsDatabaseMetadata {
             version: self.database.version.clone(),
             generated_at: self.database.generated_at.clone(),
             model: self.database.model.clone(),
+            dimension: self.dimension(),
+            normalized: self.database.normalized,
             chunk_size: self.database.chunk_size,
             overlap_size: self.database.overlap_size,
             total_files: self.database.total_files,
             total_chunks: self.database.total_chunks,
+            total_locations: self.database.chunks.iter().map(|chunk| 1 + chunk.duplicates.len()).sum(),
+            normalization_mismatch: self.normalization_mismatch,
         }
     }
 
@@ -92,8 +168,20 @@ impl SemanticSearch {
     /// Returns the top N results ranked by cosine similarity
     pub fn search(&mut self, query: &str, top_n: usize) -> Result<Vec<SearchResult>> {
         // Generate embedding for the query
-        let query_embedding = self.generator.generate_embedding(query)
+        let query_embedding = self.backend.embed_one(query)
             .context("Failed to generate query embedding")?;
+        if let Some(dimension) = self.dimension().filter(|dimension| *dimension != query_embedding.len()) {
+            bail!(
+                "the query embedding has {} dimensions but the database's have {}; it was generated with another model",
+                query_embedding.len(),
+                dimension
+            );
+        }
+        // Cosine similarity does not depend on vector length, but a difference hints at another model
+        self.normalization_mismatch = self
+            .database
+            .normalized
+            .is_some_and(|normalized| normalized != is_unit_length(&query_embedding));
 
         // Calculate similarity scores for all chunks
         let mut results: Vec<SearchResult> = self.database.chunks
@@ -104,6 +192,9 @@ impl SemanticSearch {
                     file_path: chunk.file_path.clone(),
                     content: chunk.content.clone(),
                     similarity,
+                    heading_path: chunk.metadata.as_ref().and_then(|metadata| metadata.heading_path.clone()),
+                    span: chunk.metadata.as_ref().and_then(|metadata| metadata.span),
+                    duplicates: chunk.duplicates.clone(),
                 }
             })
             .collect();
@@ -123,6 +214,19 @@ impl SemanticSearch {
     }
 }
 
+/// Fails unless `model` is the model that embedded `database`.
+fn check_model(database: &EmbeddingsDatabase, model: &str) -> Result<()> {
+    if !is_same_model(&database.model, model) {
+        bail!(
+            "the database was embedded with {} but queries would be embedded with {}; similarities between them are meaningless, so search with {}",
+            database.model,
+            model,
+            database.model
+        );
+    }
+    Ok(())
+}
+
 /// Calculate cosine similarity between two vectors
 ///
 /// Returns a value between -1 and 1, where 1 means identical direction,
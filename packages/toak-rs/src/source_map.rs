@@ -0,0 +1,290 @@
+//! Source positions and spans, and the mapping from cleaned text back to the original file, so
+//! chunks can point at the lines they came from after comments, imports and secrets were removed
+//! or replaced.
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// A position in a text: 1-based line and column (in characters) and 0-based byte offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourcePosition {
+  pub line: usize,
+  pub column: usize,
+  pub byte: usize,
+}
+
+/// A region of a text, from `start` up to but excluding `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSpan {
+  pub start: SourcePosition,
+  pub end: SourcePosition,
+}
+
+/// Line start offsets of a text, to convert between positions and (line index, byte column)
+/// pairs.
+pub(crate) struct LineIndex<'a> {
+  text: &'a str,
+  starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+  pub(crate) fn new(text: &'a str) -> Self {
+    let starts = std::iter::once(0)
+      .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    Self { text, starts }
+  }
+
+  /// Line `index` (0-based) without its line ending.
+  pub(crate) fn line(&self, index: usize) -> &'a str {
+    let Some(&start) = self.starts.get(index) else {
+      return "";
+    };
+    let end = self
+      .starts
+      .get(index + 1)
+      .map_or(self.text.len(), |next| next - 1);
+    self.text[start..end.max(start)].trim_end_matches('\r')
+  }
+
+  /// The position `column` bytes into line `index`, clamped to the line.
+  pub(crate) fn position(&self, index: usize, column: usize) -> SourcePosition {
+    let index = index.min(self.starts.len() - 1);
+    let line = self.line(index);
+    let mut column = column.min(line.len());
+    while !line.is_char_boundary(column) {
+      column -= 1;
+    }
+    SourcePosition {
+      line: index + 1,
+      column: line[..column].chars().count() + 1,
+      byte: self.starts[index] + column,
+    }
+  }
+
+  /// The position at byte `offset`.
+  pub(crate) fn position_at(&self, offset: usize) -> SourcePosition {
+    let index = self.starts.partition_point(|start| *start <= offset) - 1;
+    self.position(index, offset - self.starts[index])
+  }
+
+  /// The (line index, byte column) of a position in this text.
+  pub(crate) fn locate(&self, position: &SourcePosition) -> (usize, usize) {
+    let index = position.line.saturating_sub(1).min(self.starts.len() - 1);
+    (index, position.byte.saturating_sub(self.starts[index]))
+  }
+
+  /// The span of lines `start..end` (line indices, `end` exclusive).
+  pub(crate) fn lines_span(&self, start: usize, end: usize) -> SourceSpan {
+    let last = end.max(start + 1) - 1;
+    SourceSpan {
+      start: self.position(start, 0),
+      end: self.position(last, self.line(last).len()),
+    }
+  }
+
+  /// Moves `span`, a span within `inner`, into this text, where `inner` starts at `origin`
+  /// (line index and byte column).
+  pub(crate) fn rebase(
+    &self,
+    span: &SourceSpan,
+    inner: &LineIndex,
+    origin: (usize, usize),
+  ) -> SourceSpan {
+    let rebase = |position: &SourcePosition| {
+      let (line, column) = inner.locate(position);
+      let column = if line == 0 { column + origin.1 } else { column };
+      self.position(origin.0 + line, column)
+    };
+    SourceSpan {
+      start: rebase(&span.start),
+      end: rebase(&span.end),
+    }
+  }
+}
+
+/// Maps positions in cleaned text back to the original. Lines are aligned with a diff of their
+/// trimmed content; lines changed by cleaning or redaction are matched to the original line in
+/// the same gap that shares the longest prefix with them.
+pub struct SourceMap<'a> {
+  original: LineIndex<'a>,
+  cleaned: LineIndex<'a>,
+  lines: Vec<usize>,
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+  a.char_indices()
+    .zip(b.chars())
+    .find(|((_, x), y)| x != y)
+    .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+fn common_suffix(a: &str, b: &str) -> usize {
+  a.chars()
+    .rev()
+    .zip(b.chars().rev())
+    .take_while(|(x, y)| x == y)
+    .map(|(x, _)| x.len_utf8())
+    .sum()
+}
+
+/// Finds the text just before `offset` in `cleaned` within `original`, past their shared
+/// `prefix`, and returns the offset after it. Longer windows are tried first.
+fn find_before(cleaned: &str, original: &str, prefix: usize, offset: usize) -> Option<usize> {
+  (2..=8).rev().find_map(|len| {
+    let start = offset.checked_sub(len)?;
+    if !cleaned.is_char_boundary(start) {
+      return None;
+    }
+    let from = start.min(prefix);
+    let found = original[from..].find(&cleaned[start..offset])?;
+    Some(from + found + len)
+  })
+}
+
+impl<'a> SourceMap<'a> {
+  pub fn new(original: &'a str, cleaned: &'a str) -> Self {
+    let old: Vec<&str> = original.lines().map(str::trim).collect();
+    let new: Vec<&str> = cleaned.lines().map(str::trim).collect();
+    let last = old.len().saturating_sub(1);
+    let mut lines = vec![0; new.len()];
+
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+      match op {
+        DiffOp::Equal {
+          old_index,
+          new_index,
+          len,
+        } => {
+          for i in 0..len {
+            lines[new_index + i] = old_index + i;
+          }
+        }
+        DiffOp::Insert {
+          old_index,
+          new_index,
+          new_len,
+        } => {
+          for line in &mut lines[new_index..new_index + new_len] {
+            *line = old_index.min(last);
+          }
+        }
+        DiffOp::Replace {
+          old_index,
+          old_len,
+          new_index,
+          new_len,
+        } => {
+          let mut cursor = old_index;
+          for (i, line) in lines[new_index..new_index + new_len].iter_mut().enumerate() {
+            let best = (cursor..old_index + old_len)
+              .map(|j| (common_prefix(new[new_index + i], old[j]), j))
+              .filter(|(shared, _)| *shared > 0)
+              .max_by_key(|(shared, j)| (*shared, std::cmp::Reverse(*j)));
+            *line = best.map_or(cursor, |(_, j)| j);
+            cursor = (*line + 1).min(old_index + old_len - 1);
+          }
+        }
+        DiffOp::Delete { .. } => {}
+      }
+    }
+
+    Self {
+      original: LineIndex::new(original),
+      cleaned: LineIndex::new(cleaned),
+      lines,
+    }
+  }
+
+  /// Maps a position in the cleaned text to the original file.
+  pub fn position(&self, position: &SourcePosition) -> SourcePosition {
+    let (index, column) = self.cleaned.locate(position);
+    let Some(&original_index) = self.lines.get(index).or(self.lines.last()) else {
+      return self.original.position(0, 0);
+    };
+    let cleaned = self.cleaned.line(index);
+    let original = self.original.line(original_index);
+
+    // Columns are matched from the start of the line while the text agrees, then from its end,
+    // then by the text just before them
+    let (cleaned_indent, original_indent) = (
+      cleaned.len() - cleaned.trim_start().len(),
+      original.len() - original.trim_start().len(),
+    );
+    let (cleaned_body, original_body) = (&cleaned[cleaned_indent..], &original[original_indent..]);
+    let offset = column
+      .saturating_sub(cleaned_indent)
+      .min(cleaned_body.len());
+    let from_end = cleaned_body.len() - offset;
+    let prefix = common_prefix(cleaned_body, original_body);
+    let suffix = common_suffix(cleaned_body, original_body);
+    let column = if offset <= prefix {
+      offset
+    } else if suffix > 0 && from_end <= suffix {
+      original_body.len() - from_end
+    } else {
+      find_before(cleaned_body, original_body, prefix, offset)
+        .unwrap_or(offset.min(original_body.len()))
+    };
+    self
+      .original
+      .position(original_index, original_indent + column)
+  }
+
+  /// Maps a span in the cleaned text to the original file.
+  pub fn span(&self, span: &SourceSpan) -> SourceSpan {
+    SourceSpan {
+      start: self.position(&span.start),
+      end: self.position(&span.end),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_line_index_positions() {
+    let text = "fn main() {\r\n    let café = 1;\r\n}\r\n";
+    let index = LineIndex::new(text);
+    assert_eq!(index.line(1), "    let café = 1;");
+    let position = index.position(1, "    let café".len());
+    assert_eq!(
+      position,
+      SourcePosition {
+        line: 2,
+        column: 13,
+        byte: 26
+      }
+    );
+    assert_eq!(index.locate(&position), (1, 13));
+    assert_eq!(
+      index.lines_span(0, 3).end,
+      SourcePosition {
+        line: 3,
+        column: 2,
+        byte: 34
+      }
+    );
+  }
+
+  #[test]
+  fn test_maps_cleaned_lines_back() {
+    let original = "// License header\n\nuse std::io;\n\nfn main() { // entry\n    let key = \"sk-abcdef\"; // secret\n    run();\n}\n";
+    let cleaned = "fn main() {\nlet key = \"[REDACTED]\";\n    run();\n}";
+    let map = SourceMap::new(original, cleaned);
+    let cleaned_index = LineIndex::new(cleaned);
+
+    let span = map.span(&cleaned_index.lines_span(0, 2));
+    assert_eq!((span.start.line, span.start.column), (5, 1));
+    // The end of the redacted line maps past the closing quote, before the stripped comment
+    assert_eq!((span.end.line, span.end.column), (6, 27));
+    assert_eq!(
+      &original[span.start.byte..span.end.byte],
+      "fn main() { // entry\n    let key = \"sk-abcdef\";"
+    );
+
+    let span = map.span(&cleaned_index.lines_span(2, 4));
+    assert_eq!((span.start.line, span.end.line), (7, 8));
+  }
+}
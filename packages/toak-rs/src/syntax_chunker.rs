@@ -0,0 +1,321 @@
+//! Syntax-aware chunking: source files are parsed with tree-sitter and chunks are packed from
+//! whole top-level items (functions, impls, classes), so items are only split when they do not fit.
+use crate::source_map::LineIndex;
+use crate::text_chunker::{chunk_text, ChunkerConfig, Lines, TextChunk};
+use std::ops::Range;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser};
+
+/// Languages with a tree-sitter grammar for chunking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyntaxLanguage {
+  Rust,
+  TypeScript,
+  Tsx,
+  JavaScript,
+  Python,
+  Go,
+  Java,
+}
+
+impl SyntaxLanguage {
+  fn from_path(path: &Path) -> Option<Self> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+      "rs" => Some(Self::Rust),
+      "ts" | "mts" | "cts" => Some(Self::TypeScript),
+      "tsx" => Some(Self::Tsx),
+      "js" | "jsx" | "mjs" | "cjs" => Some(Self::JavaScript),
+      "py" | "pyi" => Some(Self::Python),
+      "go" => Some(Self::Go),
+      "java" => Some(Self::Java),
+      _ => None,
+    }
+  }
+
+  fn grammar(self) -> Language {
+    match self {
+      Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+      Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+      Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+      Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+      Self::Python => tree_sitter_python::LANGUAGE.into(),
+      Self::Go => tree_sitter_go::LANGUAGE.into(),
+      Self::Java => tree_sitter_java::LANGUAGE.into(),
+    }
+  }
+}
+
+/// Whether `file_path` is in a language the syntax-aware chunker can parse.
+pub fn supports_syntax_chunking(file_path: &str) -> bool {
+  SyntaxLanguage::from_path(Path::new(file_path)).is_some()
+}
+
+/// Whether nodes of `kind` are items worth naming as the parent of a chunk, like functions,
+/// impls and classes, as opposed to their bodies, to statements or to imports.
+fn is_item(kind: &str) -> bool {
+  let body = kind.ends_with("_list") || kind.ends_with("body") || kind.ends_with("block");
+  let import = kind.starts_with("use_") || kind.contains("import");
+  !body
+    && !import
+    && [
+      "item",
+      "definition",
+      "declaration",
+      "class",
+      "function",
+      "method",
+      "interface",
+      "module",
+    ]
+    .iter()
+    .any(|word| kind.contains(word))
+}
+
+/// The first line of an item without its opening brace or colon, e.g. `impl Widget`.
+fn item_label(node: Node, text: &str) -> Option<String> {
+  let line = text.get(node.start_byte()..)?.lines().next()?;
+  let label = line.trim().trim_end_matches(['{', ':', '(']).trim_end();
+  (!label.is_empty()).then(|| label.to_string())
+}
+
+/// A range of lines, the items enclosing it, outermost first, and the whole items it holds.
+struct ItemRange {
+  lines: Range<usize>,
+  parents: Vec<String>,
+  items: Vec<String>,
+}
+
+/// Splits the lines `start..end` covered by `node` into consecutive line ranges, one per child,
+/// recursing into children that exceed `budget`. Text between children joins the following range.
+fn item_ranges(
+  node: Node,
+  start: usize,
+  end: usize,
+  source: &Source,
+  parents: &[String],
+) -> Vec<ItemRange> {
+  let mut ranges = Vec::new();
+  let mut cursor = start;
+  let mut walker = node.walk();
+
+  for child in node.children(&mut walker) {
+    let child_end = (child.end_position().row + 1).min(end);
+    if child_end <= cursor {
+      continue;
+    }
+    let range = cursor..child_end;
+    let nested = child.child_count() > 0 && child.start_position().row < child.end_position().row;
+    if source.lines.tokens(&range) > source.budget && nested {
+      let mut parents = parents.to_vec();
+      if is_item(child.kind()) {
+        parents.extend(item_label(child, source.text));
+      }
+      ranges.extend(item_ranges(child, cursor, child_end, source, &parents));
+    } else {
+      let items = if is_item(child.kind()) {
+        item_label(child, source.text).into_iter().collect()
+      } else {
+        Vec::new()
+      };
+      ranges.push(ItemRange {
+        lines: range,
+        parents: parents.to_vec(),
+        items,
+      });
+    }
+    cursor = child_end;
+  }
+  if cursor < end {
+    ranges.push(ItemRange {
+      lines: cursor..end,
+      parents: parents.to_vec(),
+      items: Vec::new(),
+    });
+  }
+  ranges
+}
+
+/// The text being chunked, with its per-line token counts and the chunk budget.
+struct Source<'a> {
+  text: &'a str,
+  lines: Lines<'a>,
+  budget: usize,
+}
+
+/// Chunks `text` along the syntax tree of its language. Returns `None` if the language is not
+/// supported or the text cannot be parsed, in which case the line chunker should be used.
+pub(crate) fn chunk_syntax(
+  text: &str,
+  file_path: &str,
+  config: &ChunkerConfig,
+) -> Option<Vec<TextChunk>> {
+  let language = SyntaxLanguage::from_path(Path::new(file_path))?;
+  let mut parser = Parser::new();
+  parser.set_language(&language.grammar()).ok()?;
+  let tree = parser.parse(text, None)?;
+
+  let source = Source {
+    text,
+    lines: Lines::new(text, config),
+    budget: config.chunk_size,
+  };
+  let lines = &source.lines;
+  let index = LineIndex::new(text);
+  let ranges = item_ranges(tree.root_node(), 0, lines.lines.len(), &source, &[]);
+
+  // Pack consecutive items greedily up to the budget, under the items they all share
+  let mut packed: Vec<ItemRange> = Vec::new();
+  for range in ranges {
+    match packed.last_mut() {
+      Some(last) if lines.tokens(&(last.lines.start..range.lines.end)) <= config.chunk_size => {
+        last.lines.end = range.lines.end;
+        last.items.extend(range.items);
+        let shared = last
+          .parents
+          .iter()
+          .zip(&range.parents)
+          .take_while(|(a, b)| a == b)
+          .count();
+        last.parents.truncate(shared);
+      }
+      _ => packed.push(range),
+    }
+  }
+
+  let mut chunks = Vec::new();
+  for ItemRange {
+    lines: range,
+    parents,
+    items,
+  } in packed
+  {
+    let content = lines.text(&range);
+    if content.trim().is_empty() {
+      continue;
+    }
+    // Chunks outside any item are named after the items they hold
+    let parent = if !parents.is_empty() {
+      Some(parents.join(" > "))
+    } else {
+      (!items.is_empty()).then(|| items.join(", "))
+    };
+    if lines.tokens(&range) <= config.chunk_size {
+      chunks.push(TextChunk {
+        content,
+        start_index: range.start,
+        end_index: range.end,
+        chunk_index: chunks.len(),
+        span: index.lines_span(range.start, range.end),
+        heading_path: None,
+        parent,
+      });
+    } else {
+      // Items without nested structure to split at, such as long literals
+      let content_index = LineIndex::new(&content);
+      for piece in chunk_text(&content, config) {
+        chunks.push(TextChunk {
+          content: piece.content,
+          start_index: range.start + piece.start_index,
+          end_index: (range.start + piece.end_index).min(range.end),
+          chunk_index: chunks.len(),
+          span: index.rebase(&piece.span, &content_index, (range.start, 0)),
+          heading_path: None,
+          parent: parent.clone(),
+        });
+      }
+    }
+  }
+  Some(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::text_chunker::ChunkingStrategy;
+
+  fn config(chunk_size: usize) -> ChunkerConfig {
+    ChunkerConfig {
+      chunk_size,
+      overlap_size: 0,
+      strategy: ChunkingStrategy::Syntax,
+      tokenizer: None,
+    }
+  }
+
+  fn function(name: &str, statements: usize) -> String {
+    let body: String = (0..statements)
+      .map(|i| format!("    let v{} = {} + 1;\n", i, i))
+      .collect();
+    format!("fn {}() {{\n{}}}\n", name, body)
+  }
+
+  #[test]
+  fn test_packs_whole_functions() {
+    // Each function is 3 + 2 * 6 + 1 = 16 tokens
+    let source = format!(
+      "{}\n{}\n{}",
+      function("a", 2),
+      function("b", 2),
+      function("c", 2)
+    );
+    let chunks = chunk_syntax(&source, "src/lib.rs", &config(40)).unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert!(chunks[0].content.starts_with("fn a()") && chunks[0].content.contains("fn b()"));
+    assert_eq!(chunks[0].parent.as_deref(), Some("fn a(), fn b()"));
+    assert_eq!(chunks[1].parent.as_deref(), Some("fn c()"));
+    assert!(chunks[0].content.trim_end().ends_with('}'));
+    assert!(chunks[1].content.trim_start().starts_with("fn c()"));
+    // The blank line before an item belongs to it
+    assert_eq!((chunks[1].start_index, chunks[1].end_index), (9, 14));
+    assert_eq!(
+      (chunks[1].span.start.line, chunks[1].span.end.line),
+      (10, 14)
+    );
+  }
+
+  #[test]
+  fn test_splits_oversized_items_at_blocks() {
+    let source = format!(
+      "impl Widget {{\n{}\n{}}}\n",
+      function("first", 4).replace('\n', "\n    "),
+      function("second", 4).replace('\n', "\n    ")
+    );
+    let chunks = chunk_syntax(&source, "widget.rs", &config(40)).unwrap();
+    assert!(chunks.len() >= 2);
+    assert!(chunks
+      .iter()
+      .any(|c| c.content.contains("fn first()") && c.content.contains("let v3")));
+    assert!(chunks
+      .iter()
+      .all(|c| !(c.content.contains("fn first()") && c.content.contains("fn second()"))));
+    assert!(chunks.iter().all(|c| c
+      .parent
+      .as_deref()
+      .is_some_and(|parent| parent.starts_with("impl Widget"))));
+    assert_eq!(chunks[0].parent.as_deref(), Some("impl Widget"));
+    let rejoined: String = chunks.iter().map(|c| c.content.as_str()).collect();
+    assert_eq!(rejoined, source);
+  }
+
+  #[test]
+  fn test_small_files_name_their_items() {
+    let source = function("small", 1);
+    let chunks = crate::text_chunker::chunk_file(&source, "src/lib.rs", &config(40));
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].content, source);
+    assert_eq!(chunks[0].parent.as_deref(), Some("fn small()"));
+  }
+
+  #[test]
+  fn test_other_languages_and_python() {
+    assert!(chunk_syntax("key: value", "config.yaml", &config(40)).is_none());
+
+    let source = "class Greeter:\n    def hello(self):\n        return 'hi'\n\n\ndef main():\n    Greeter().hello()\n";
+    let chunks = chunk_syntax(source, "app.py", &config(6)).unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert!(chunks[1]
+      .content
+      .contains("def main():\n    Greeter().hello()"));
+  }
+}
@@ -1,13 +1,68 @@
 //! Helpers for slicing strings into token-aware chunks for embeddings and documentation.
+use crate::prose_chunker::chunk_prose;
+use crate::source_map::{SourcePosition, SourceSpan};
+#[cfg(feature = "syntax")]
+use crate::syntax_chunker::chunk_syntax;
 use crate::token_cleaner::count_tokens;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How files are split into chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkingStrategy {
+    /// Fill chunks line by line, overlapping consecutive chunks
+    Lines,
+    /// Pack whole functions, impls and classes in Rust, TypeScript/JavaScript, Python, Go and
+    /// Java, splitting oversized items at nested blocks, and split Markdown, reStructuredText and
+    /// AsciiDoc documents by section; other files use `Lines`. Source files are chunked by the
+    /// syntax tree only with the `syntax` feature
+    #[default]
+    Syntax,
+}
+
+impl std::str::FromStr for ChunkingStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lines" => Ok(Self::Lines),
+            "syntax" => Ok(Self::Syntax),
+            other => Err(format!("unknown chunking strategy '{}' (expected lines or syntax)", other)),
+        }
+    }
+}
+
+/// Counts tokens the way an embedding model's tokenizer does, so chunks can be sized to fit its
+/// input.
+pub trait TokenCounter: Send + Sync {
+    /// Number of tokens in `text`, without the special tokens the model adds around its input
+    fn count_tokens(&self, text: &str) -> usize;
+}
 
 /// Configuration for text chunking
 #[derive(Clone)]
 pub struct ChunkerConfig {
     /// Target size for each chunk in tokens
     pub chunk_size: usize,
-    /// Number of tokens to overlap between chunks for context preservation
+    /// Number of tokens to overlap between chunks for context preservation; syntax-aware chunks
+    /// do not overlap
     pub overlap_size: usize,
+    /// How chunk boundaries are chosen for source files
+    pub strategy: ChunkingStrategy,
+    /// Tokenizer the sizes are measured with; `None` counts whitespace-separated words
+    pub tokenizer: Option<Arc<dyn TokenCounter>>,
+}
+
+impl ChunkerConfig {
+    /// Counts the tokens of `text` with the configured tokenizer.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        match &self.tokenizer {
+            Some(tokenizer) => tokenizer.count_tokens(text),
+            None => count_tokens(text),
+        }
+    }
 }
 
 impl Default for ChunkerConfig {
@@ -15,6 +70,8 @@ impl Default for ChunkerConfig {
         Self {
             chunk_size: 800,
             overlap_size: 100,
+            strategy: ChunkingStrategy::default(),
+            tokenizer: None,
         }
     }
 }
@@ -23,131 +80,331 @@ impl Default for ChunkerConfig {
 #[derive(Debug, Clone)]
 pub struct TextChunk {
     pub content: String,
+    /// First line of the chunk (0-based) in the chunked text
     pub start_index: usize,
+    /// Line after the last line of the chunk (exclusive) in the chunked text
     pub end_index: usize,
     pub chunk_index: usize,
+    /// Exact region of the chunked text the content was taken from
+    pub span: SourceSpan,
+    /// Titles of the enclosing document sections, e.g. `Install > Linux > Packages`
+    pub heading_path: Option<String>,
+    /// Signatures of the enclosing code items, e.g. `impl Widget > fn draw(&self)`
+    pub parent: Option<String>,
 }
 
 /// Chunks text into overlapping segments based on token count
 pub fn chunk_text(text: &str, config: &ChunkerConfig) -> Vec<TextChunk> {
-    if text.trim().is_empty() {
-        return vec![];
+    // Reading from memory cannot fail
+    Chunker::new(text.as_bytes(), config.clone()).map_while(Result::ok).collect()
+}
+
+/// Lines longer than this are read and split in pieces, so a single huge line does not have to
+/// fit in memory.
+const MAX_LINE_BYTES: usize = 1 << 20;
+
+/// A line, or a piece of an overlong line, read by the [`Chunker`].
+struct Line {
+    text: String,
+    tokens: usize,
+    index: usize,
+    start: SourcePosition,
+    end: SourcePosition,
+}
+
+/// The position `text.len()` bytes after `position` on the same line.
+fn advance(position: SourcePosition, text: &str) -> SourcePosition {
+    SourcePosition {
+        line: position.line,
+        column: position.column + text.chars().count(),
+        byte: position.byte + text.len(),
     }
+}
 
-    // If the entire text fits in one chunk, return it as-is
-    let total_tokens = count_tokens(text);
-    if total_tokens <= config.chunk_size {
-        return vec![TextChunk {
-            content: text.to_string(),
-            start_index: 0,
-            end_index: text.len(),
+/// Lazily chunks text read line by line from `reader`, holding about one chunk of text in
+/// memory. Produces the same chunks as [`chunk_text`], except that lines over [`MAX_LINE_BYTES`]
+/// are always split by characters.
+pub struct Chunker<R> {
+    reader: R,
+    config: ChunkerConfig,
+    /// Bytes of a character cut by the line length limit, completed by the next read
+    carry: Vec<u8>,
+    line_index: usize,
+    /// Where the next piece of input starts
+    position: SourcePosition,
+    /// Input read while it may still fit a single chunk: raw text, lines and their tokens
+    head: Option<(String, Vec<Line>, usize)>,
+    current: String,
+    current_tokens: usize,
+    current_span: Option<SourceSpan>,
+    start_line: usize,
+    overlap: VecDeque<Line>,
+    overlap_tokens: usize,
+    chunk_index: usize,
+    ready: VecDeque<TextChunk>,
+    done: bool,
+}
+
+impl<R: BufRead> Chunker<R> {
+    pub fn new(reader: R, config: ChunkerConfig) -> Self {
+        Self {
+            reader,
+            config,
+            carry: Vec::new(),
+            line_index: 0,
+            position: SourcePosition { line: 1, column: 1, byte: 0 },
+            head: Some((String::new(), Vec::new(), 0)),
+            current: String::new(),
+            current_tokens: 0,
+            current_span: None,
+            start_line: 0,
+            overlap: VecDeque::new(),
+            overlap_tokens: 0,
             chunk_index: 0,
-        }];
+            ready: VecDeque::new(),
+            done: false,
+        }
     }
 
-    let mut chunks = Vec::new();
-    let lines: Vec<&str> = text.lines().collect();
-    let mut current_chunk = String::new();
-    let mut current_tokens = 0;
-    let mut start_line = 0;
-    let mut chunk_index = 0;
-    let mut overlap_buffer = String::new();
+    /// Reads the next line, or up to [`MAX_LINE_BYTES`] of it. Returns the raw text read and
+    /// whether it reaches the end of its line.
+    fn read_piece(&mut self) -> io::Result<Option<(String, bool)>> {
+        let mut bytes = std::mem::take(&mut self.carry);
+        let mut line_end = false;
+        while bytes.len() < MAX_LINE_BYTES {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                line_end = true;
+                break;
+            }
+            let room = buffer.len().min(MAX_LINE_BYTES - bytes.len());
+            if let Some(newline) = buffer[..room].iter().position(|b| *b == b'\n') {
+                bytes.extend_from_slice(&buffer[..=newline]);
+                self.reader.consume(newline + 1);
+                line_end = true;
+                break;
+            }
+            bytes.extend_from_slice(&buffer[..room]);
+            self.reader.consume(room);
+        }
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        if !line_end {
+            // Keep a character cut at the limit for the next piece
+            if let Err(error) = std::str::from_utf8(&bytes) {
+                if error.error_len().is_none() {
+                    self.carry = bytes.split_off(error.valid_up_to());
+                }
+            }
+        }
+        let text = String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(Some((text, line_end)))
+    }
 
-    for (line_idx, line) in lines.iter().enumerate() {
-        let line_with_newline = format!("{}\n", line);
-        let line_tokens = count_tokens(&line_with_newline);
+    fn emit(&mut self, content: String, start_index: usize, end_index: usize, span: SourceSpan) {
+        self.ready.push_back(TextChunk {
+            content,
+            start_index,
+            end_index,
+            chunk_index: self.chunk_index,
+            span,
+            heading_path: None,
+            parent: None,
+        });
+        self.chunk_index += 1;
+    }
+
+    fn emit_current(&mut self, end_index: usize) {
+        let content = std::mem::take(&mut self.current);
+        if let Some(span) = self.current_span.take() {
+            self.emit(content, self.start_line, end_index, span);
+        }
+    }
 
+    /// Adds a line to the current chunk, starting a new chunk with the overlap when it is full.
+    fn push_line(&mut self, line: Line, whole: bool) {
         // If a single line is too large, we need to split it by characters
-        if line_tokens > config.chunk_size {
-            // First, finish current chunk if it has content
-            if !current_chunk.is_empty() {
-                chunks.push(TextChunk {
-                    content: current_chunk.clone(),
-                    start_index: start_line,
-                    end_index: line_idx,
-                    chunk_index,
-                });
-                chunk_index += 1;
+        if !whole || line.tokens > self.config.chunk_size {
+            if !self.current.is_empty() {
+                self.emit_current(line.index);
             }
-
-            // Split the large line into character-based chunks
-            let char_chunks = split_large_line(line, config);
-            for char_chunk in char_chunks {
-                chunks.push(TextChunk {
-                    content: char_chunk,
-                    start_index: line_idx,
-                    end_index: line_idx + 1,
-                    chunk_index,
-                });
-                chunk_index += 1;
+            let mut position = line.start;
+            for piece in split_large_line(&line.text, &self.config) {
+                let end = advance(position, &piece);
+                self.emit(piece, line.index, line.index + 1, SourceSpan { start: position, end });
+                position = end;
             }
+            self.current.clear();
+            self.current_tokens = 0;
+            self.current_span = None;
+            self.start_line = line.index + 1;
+            self.overlap.clear();
+            self.overlap_tokens = 0;
+            return;
+        }
 
-            // Reset for next chunk
-            current_chunk.clear();
-            current_tokens = 0;
-            start_line = line_idx + 1;
-            overlap_buffer.clear();
-            continue;
-        }
-
-        // Check if adding this line would exceed chunk size
-        if current_tokens + line_tokens > config.chunk_size && !current_chunk.is_empty() {
-            // Save current chunk
-            chunks.push(TextChunk {
-                content: current_chunk.clone(),
-                start_index: start_line,
-                end_index: line_idx,
-                chunk_index,
-            });
-            chunk_index += 1;
+        if self.current_tokens + line.tokens > self.config.chunk_size && !self.current.is_empty() {
+            self.emit_current(line.index);
 
             // Start new chunk with overlap from previous chunk
-            current_chunk = overlap_buffer.clone();
-            current_tokens = count_tokens(&current_chunk);
-            start_line = line_idx;
+            self.current = self.overlap.iter().map(|overlap| format!("{}\n", overlap.text)).collect();
+            self.current_tokens = self.overlap_tokens;
+            self.start_line = self.overlap.front().map_or(line.index, |overlap| overlap.index);
+            self.current_span = self.overlap.front().map(|front| SourceSpan {
+                start: front.start,
+                end: self.overlap.back().map_or(front.end, |back| back.end),
+            });
         }
 
-        // Add line to current chunk
-        current_chunk.push_str(&line_with_newline);
-        current_tokens += line_tokens;
+        self.current.push_str(&line.text);
+        self.current.push('\n');
+        self.current_tokens += line.tokens;
+        let start = self.current_span.map_or(line.start, |span| span.start);
+        self.current_span = Some(SourceSpan { start, end: line.end });
+
+        // Keep the last lines within the overlap budget
+        self.overlap_tokens += line.tokens;
+        self.overlap.push_back(line);
+        while self.overlap_tokens > self.config.overlap_size {
+            match self.overlap.pop_front() {
+                Some(dropped) => self.overlap_tokens -= dropped.tokens,
+                None => break,
+            }
+        }
+    }
 
-        // Update overlap buffer (keep last N tokens worth of lines)
-        overlap_buffer.push_str(&line_with_newline);
-        let overlap_tokens = count_tokens(&overlap_buffer);
+    /// Reads the next piece of input and chunks it, or finishes at the end of the input.
+    fn step(&mut self) -> io::Result<()> {
+        let Some((raw, line_end)) = self.read_piece()? else {
+            self.finish();
+            return Ok(());
+        };
+        let text = if line_end {
+            raw.strip_suffix('\n').map_or(raw.as_str(), |text| text.strip_suffix('\r').unwrap_or(text))
+        } else {
+            raw.as_str()
+        };
+        let start = self.position;
+        let line = Line {
+            tokens: self.config.count_tokens(&format!("{}\n", text)),
+            text: text.to_string(),
+            index: self.line_index,
+            start,
+            end: advance(start, text),
+        };
+        if line_end {
+            self.line_index += 1;
+            self.position = SourcePosition { line: self.line_index + 1, column: 1, byte: start.byte + raw.len() };
+        } else {
+            self.position = line.end;
+        }
+        // A piece of an overlong line continues the line read before it
+        let whole = line_end && start.column == 1;
+
+        if let Some((head, lines, tokens)) = &mut self.head {
+            head.push_str(&raw);
+            *tokens += line.tokens;
+            let overflowing = *tokens > self.config.chunk_size || !whole;
+            lines.push(line);
+            if overflowing {
+                self.replay_head();
+            }
+            return Ok(());
+        }
+        self.push_line(line, whole);
+        Ok(())
+    }
 
-        // Trim overlap buffer if it's too large
-        if overlap_tokens > config.overlap_size {
-            let overlap_lines: Vec<&str> = overlap_buffer.lines().collect();
-            let mut new_overlap = String::new();
-            let mut overlap_tok = 0;
+    /// Chunks the lines read so far, once the input is known not to fit a single chunk.
+    fn replay_head(&mut self) {
+        if let Some((_, lines, _)) = self.head.take() {
+            for line in lines {
+                let whole = line.start.column == 1 && line.index < self.line_index;
+                self.push_line(line, whole);
+            }
+        }
+    }
 
-            for ol in overlap_lines.iter().rev() {
-                let ol_with_newline = format!("{}\n", ol);
-                let ol_tokens = count_tokens(&ol_with_newline);
+    fn finish(&mut self) {
+        self.done = true;
+        if let Some((head, lines, tokens)) = self.head.take() {
+            if head.trim().is_empty() {
+                return;
+            }
+            // If the entire text fits in one chunk, return it as-is
+            if self.config.count_tokens(&head) <= self.config.chunk_size {
+                let span = SourceSpan {
+                    start: lines.first().map_or(self.position, |line| line.start),
+                    end: lines.last().map_or(self.position, |line| line.end),
+                };
+                self.emit(head, 0, self.line_index, span);
+                return;
+            }
+            self.head = Some((head, lines, tokens));
+            self.replay_head();
+        }
+        // Add final chunk if there's remaining content
+        if !self.current.is_empty() {
+            self.emit_current(self.line_index);
+        }
+    }
+}
 
-                if overlap_tok + ol_tokens > config.overlap_size {
-                    break;
-                }
+impl<R: BufRead> Iterator for Chunker<R> {
+    type Item = io::Result<TextChunk>;
 
-                new_overlap = format!("{}{}", ol_with_newline, new_overlap);
-                overlap_tok += ol_tokens;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(chunk) = self.ready.pop_front() {
+                return Some(Ok(chunk));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(error) = self.step() {
+                self.done = true;
+                return Some(Err(error));
             }
-
-            overlap_buffer = new_overlap;
         }
     }
+}
 
-    // Add final chunk if there's remaining content
-    if !current_chunk.is_empty() {
-        chunks.push(TextChunk {
-            content: current_chunk,
-            start_index: start_line,
-            end_index: lines.len(),
-            chunk_index,
-        });
+/// Per-line token counts of the text being chunked.
+pub(crate) struct Lines<'a> {
+    pub(crate) lines: Vec<&'a str>,
+    tokens: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(text: &'a str, config: &ChunkerConfig) -> Self {
+        let lines: Vec<&str> = text.lines().collect();
+        let tokens = lines.iter().map(|line| config.count_tokens(line)).collect();
+        Self { lines, tokens }
+    }
+
+    pub(crate) fn tokens(&self, range: &Range<usize>) -> usize {
+        self.tokens[range.clone()].iter().sum()
     }
 
-    chunks
+    pub(crate) fn text(&self, range: &Range<usize>) -> String {
+        self.lines[range.clone()].iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+/// Chunks the content of `file_path` using the configured strategy.
+pub fn chunk_file(text: &str, file_path: &str, config: &ChunkerConfig) -> Vec<TextChunk> {
+    if config.strategy == ChunkingStrategy::Syntax {
+        // Chunks carry their heading path or items, so files go through these whatever their size
+        if let Some(chunks) = chunk_prose(text, file_path, config) {
+            return chunks;
+        }
+        #[cfg(feature = "syntax")]
+        if let Some(chunks) = chunk_syntax(text, file_path, config) {
+            return chunks;
+        }
+    }
+    chunk_text(text, config)
 }
 
 /// Splits a very large line into smaller chunks based on character count
@@ -164,7 +421,7 @@ fn split_large_line(line: &str, config: &ChunkerConfig) -> Vec<String> {
         let chunk: String = chars[start..end].iter().collect();
 
         // Verify the chunk isn't too large
-        if count_tokens(&chunk) <= config.chunk_size || result.is_empty() {
+        if config.count_tokens(&chunk) <= config.chunk_size || result.is_empty() {
             result.push(chunk);
             start = end;
         } else {
@@ -204,6 +461,7 @@ mod tests {
         let config = ChunkerConfig {
             chunk_size: 50,
             overlap_size: 10,
+            ..Default::default()
         };
         let text = (0..100).map(|i| format!("Line {}", i)).collect::<Vec<_>>().join("\n");
         let chunks = chunk_text(&text, &config);
@@ -216,4 +474,93 @@ mod tests {
             assert_eq!(chunk.chunk_index, i);
         }
     }
+
+    #[test]
+    fn test_spans_cover_chunk_content() {
+        let config = ChunkerConfig {
+            chunk_size: 20,
+            overlap_size: 4,
+            ..Default::default()
+        };
+        let text = (0..30).map(|i| format!("Line {}", i)).collect::<Vec<_>>().join("\n");
+        let chunks = chunk_text(&text, &config);
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.span.start.byte..chunk.span.end.byte], chunk.content.trim_end());
+            assert_eq!((chunk.span.start.line, chunk.span.end.line), (chunk.start_index + 1, chunk.end_index));
+        }
+        // Later chunks start at their overlap
+        assert!(chunks[1].start_index < chunks[0].end_index);
+
+        let single = chunk_text("one\ntwo\n", &ChunkerConfig::default());
+        assert_eq!((single[0].start_index, single[0].end_index), (0, 2));
+        assert_eq!((single[0].span.end.line, single[0].span.end.column), (2, 4));
+    }
+
+    /// Counts characters, standing in for a subword tokenizer
+    struct CharCounter;
+
+    impl TokenCounter for CharCounter {
+        fn count_tokens(&self, text: &str) -> usize {
+            text.trim_end().chars().count()
+        }
+    }
+
+    #[test]
+    fn test_sizes_chunks_with_tokenizer() {
+        let text = (0..20).map(|i| format!("word{:02}", i)).collect::<Vec<_>>().join("\n");
+        let words = ChunkerConfig {
+            chunk_size: 20,
+            overlap_size: 0,
+            ..Default::default()
+        };
+        assert_eq!(chunk_text(&text, &words).len(), 1);
+
+        let chars = ChunkerConfig {
+            tokenizer: Some(Arc::new(CharCounter)),
+            ..words
+        };
+        let chunks = chunk_text(&text, &chars);
+        assert_eq!(chunks.len(), 7);
+        assert!(chunks.iter().all(|chunk| chars.count_tokens(&chunk.content) <= 20));
+    }
+
+    #[test]
+    fn test_streaming_matches_chunk_text() {
+        let config = ChunkerConfig {
+            chunk_size: 30,
+            overlap_size: 8,
+            ..Default::default()
+        };
+        let text = (0..200).map(|i| format!("line {} of the log\r\n", i)).collect::<String>();
+        let reader = io::BufReader::with_capacity(7, text.as_bytes());
+        let streamed: Vec<TextChunk> = Chunker::new(reader, config.clone()).collect::<io::Result<_>>().unwrap();
+        let chunks = chunk_text(&text, &config);
+        assert_eq!(streamed.len(), chunks.len());
+        for (streamed, chunk) in streamed.iter().zip(&chunks) {
+            assert_eq!((&streamed.content, streamed.start_index, streamed.span), (&chunk.content, chunk.start_index, chunk.span));
+        }
+        assert!(Chunker::new(&b"ok\n\xff\n"[..], config).any(|chunk| chunk.is_err()));
+    }
+
+    #[test]
+    fn test_streams_unbounded_input() {
+        let config = ChunkerConfig {
+            chunk_size: 10,
+            ..Default::default()
+        };
+        // An endless line is read in pieces and chunked lazily
+        let chunks: Vec<TextChunk> = Chunker::new(io::BufReader::new(io::repeat(b'x')), config.clone())
+            .take(3)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].span.start.column, chunks[0].span.end.column);
+
+        // Characters cut at the line length limit are completed from the next piece
+        let line = format!("a{}", "é".repeat(MAX_LINE_BYTES / 2 + 10));
+        let chunks: Vec<TextChunk> = Chunker::new(line.as_bytes(), config).map(Result::unwrap).collect();
+        assert_eq!(chunks.iter().map(|chunk| chunk.content.as_str()).collect::<String>(), line);
+        let last = chunks.last().unwrap();
+        assert_eq!((last.span.end.byte, last.span.end.column), (line.len(), line.chars().count() + 1));
+    }
 }
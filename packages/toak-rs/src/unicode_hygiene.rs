@@ -0,0 +1,348 @@
+//! Detection and neutralisation of Trojan-Source style Unicode: bidirectional control
+//! characters, invisible characters, and identifiers that mix Latin with look-alike Cyrillic or
+//! Greek letters.
+use crate::token_cleaner::{FingerprintKey, RedactionFinding, Severity};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// What the hygiene stage does with suspicious Unicode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodePolicy {
+  /// Remove bidi and invisible characters; rewrite confusable letters in mixed-script
+  /// identifiers to their Latin look-alikes
+  #[default]
+  Strip,
+  /// Replace bidi and invisible characters with visible `<U+XXXX>` escapes; leave identifiers
+  Escape,
+  /// Leave the text untouched and only report findings
+  ReportOnly,
+}
+
+impl std::str::FromStr for UnicodePolicy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "strip" => Ok(Self::Strip),
+      "escape" => Ok(Self::Escape),
+      "report" | "report-only" => Ok(Self::ReportOnly),
+      other => Err(format!(
+        "unknown unicode policy '{}' (expected strip, escape or report)",
+        other
+      )),
+    }
+  }
+}
+
+/// Bidirectional formatting characters abused to reorder how code is displayed.
+fn is_bidi_control(c: char) -> bool {
+  matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{200E}' | '\u{200F}' | '\u{061C}')
+}
+
+/// Characters that render as nothing. Joiners are only suspicious next to letters or digits,
+/// since they legitimately glue emoji sequences together.
+fn is_invisible(c: char, prev: Option<char>, next: Option<char>) -> bool {
+  match c {
+    '\u{200B}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' | '\u{180E}' | '\u{2062}'..='\u{2064}' => {
+      true
+    }
+    '\u{200C}' | '\u{200D}' => {
+      prev.is_some_and(char::is_alphanumeric) || next.is_some_and(char::is_alphanumeric)
+    }
+    _ => false,
+  }
+}
+
+fn is_latin_letter(c: char) -> bool {
+  c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}')
+}
+
+fn is_cyrillic_or_greek(c: char) -> bool {
+  matches!(c, '\u{0370}'..='\u{03FF}' | '\u{0400}'..='\u{04FF}')
+}
+
+/// Latin look-alike for common Cyrillic and Greek confusables.
+fn latin_confusable(c: char) -> Option<char> {
+  let mapped = match c {
+    'а' | 'α' => 'a',
+    'А' | 'Α' => 'A',
+    'В' | 'Β' => 'B',
+    'с' | 'ϲ' => 'c',
+    'С' => 'C',
+    'е' | 'ε' => 'e',
+    'Е' | 'Ε' => 'E',
+    'Н' | 'Η' => 'H',
+    'і' | 'ι' => 'i',
+    'І' | 'Ι' => 'I',
+    'ј' => 'j',
+    'Ј' => 'J',
+    'К' | 'Κ' => 'K',
+    'М' | 'Μ' => 'M',
+    'о' | 'ο' => 'o',
+    'О' | 'Ο' => 'O',
+    'р' | 'ρ' => 'p',
+    'Р' | 'Ρ' => 'P',
+    'ѕ' => 's',
+    'Ѕ' => 'S',
+    'Т' | 'Τ' => 'T',
+    'υ' => 'u',
+    'ν' => 'v',
+    'х' | 'χ' => 'x',
+    'Х' | 'Χ' => 'X',
+    'у' => 'y',
+    'У' | 'Υ' => 'Y',
+    'Ζ' => 'Z',
+    _ => return None,
+  };
+  Some(mapped)
+}
+
+static IDENTIFIER_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+fn identifier_pattern() -> &'static Regex {
+  IDENTIFIER_PATTERN
+    .get_or_init(|| Regex::new(r"[\p{L}\p{N}_]+").expect("valid identifier pattern"))
+}
+
+fn finding(
+  file_path: &str,
+  line: usize,
+  column: usize,
+  rule_id: &str,
+  severity: Severity,
+  value: &str,
+  key: &FingerprintKey,
+) -> RedactionFinding {
+  RedactionFinding {
+    file_path: file_path.to_string(),
+    line,
+    column,
+    rule_id: rule_id.to_string(),
+    severity,
+    fingerprint: key.fingerprint(value),
+  }
+}
+
+/// Maps columns of sanitized text back to the input text. Sanitizing never adds or removes
+/// lines, so only columns move: each line lists the sanitized columns from which the offset to
+/// the input column changes.
+#[derive(Debug, Default)]
+pub(crate) struct ColumnMap {
+  shifts: Vec<Vec<(usize, isize)>>,
+}
+
+impl ColumnMap {
+  /// The input column of the 1-based `column` on the 1-based `line` of the sanitized text.
+  pub(crate) fn original_column(&self, line: usize, column: usize) -> usize {
+    let offset = self
+      .shifts
+      .get(line.saturating_sub(1))
+      .and_then(|shifts| shifts.iter().rev().find(|(start, _)| *start <= column))
+      .map_or(0, |(_, offset)| *offset);
+    column.saturating_add_signed(offset)
+  }
+}
+
+/// Runs the hygiene stage over `text`, returning the text rewritten according to `policy` and
+/// a finding per suspicious character or identifier. Positions refer to the input text.
+/// A byte order mark at the very start of the input is dropped silently.
+pub fn sanitize_unicode(
+  text: &str,
+  file_path: &str,
+  policy: UnicodePolicy,
+) -> (String, Vec<RedactionFinding>) {
+  let (output, findings, _) =
+    sanitize_unicode_mapped(text, file_path, policy, &FingerprintKey::default());
+  (output, findings)
+}
+
+/// [`sanitize_unicode`] with findings fingerprinted under `key`, also returning where the
+/// columns of the output were in the input.
+pub(crate) fn sanitize_unicode_mapped(
+  text: &str,
+  file_path: &str,
+  policy: UnicodePolicy,
+  key: &FingerprintKey,
+) -> (String, Vec<RedactionFinding>, ColumnMap) {
+  let bom = text.starts_with('\u{FEFF}');
+  let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+  let mut findings = Vec::new();
+  let mut output = String::with_capacity(text.len());
+  let mut columns = ColumnMap::default();
+
+  for (line_idx, line) in text.split('\n').enumerate() {
+    if line_idx > 0 {
+      output.push('\n');
+    }
+    // Input columns of this line are shifted by the dropped byte order mark
+    let lead = usize::from(bom && line_idx == 0);
+    let mut shifts = if lead > 0 { vec![(1, 1)] } else { Vec::new() };
+    let mut emitted = 0;
+
+    // Mixed-script identifiers, reported once each and optionally normalized
+    let mut normalized_spans: Vec<(usize, usize, String)> = Vec::new();
+    for m in identifier_pattern().find_iter(line) {
+      let ident = m.as_str();
+      if ident.chars().any(is_latin_letter) && ident.chars().any(is_cyrillic_or_greek) {
+        let column = line[..m.start()].chars().count() + lead + 1;
+        findings.push(finding(
+          file_path,
+          line_idx + 1,
+          column,
+          "unicode-homoglyph",
+          Severity::Medium,
+          ident,
+          key,
+        ));
+        if policy == UnicodePolicy::Strip {
+          let normalized: String = ident
+            .chars()
+            .map(|c| latin_confusable(c).unwrap_or(c))
+            .collect();
+          normalized_spans.push((m.start(), m.end(), normalized));
+        }
+      }
+    }
+
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut spans = normalized_spans.into_iter().peekable();
+    let mut i = 0;
+    while i < chars.len() {
+      let (offset, c) = chars[i];
+      if let Some((start, end, normalized)) = spans.peek() {
+        if *start == offset {
+          // Confusables map one to one, so columns keep their offset
+          output.push_str(normalized);
+          emitted += normalized.chars().count();
+          let end = *end;
+          spans.next();
+          while i < chars.len() && chars[i].0 < end {
+            i += 1;
+          }
+          continue;
+        }
+      }
+
+      let prev = i.checked_sub(1).map(|p| chars[p].1);
+      let next = chars.get(i + 1).map(|(_, n)| *n);
+      let rule = if is_bidi_control(c) {
+        Some(("unicode-bidi", Severity::High))
+      } else if is_invisible(c, prev, next) {
+        Some(("unicode-invisible", Severity::Medium))
+      } else {
+        None
+      };
+
+      match rule {
+        Some((rule_id, severity)) => {
+          findings.push(finding(
+            file_path,
+            line_idx + 1,
+            i + lead + 1,
+            rule_id,
+            severity,
+            &c.to_string(),
+            key,
+          ));
+          let replacement = match policy {
+            UnicodePolicy::Strip => String::new(),
+            UnicodePolicy::Escape => format!("<U+{:04X}>", c as u32),
+            UnicodePolicy::ReportOnly => c.to_string(),
+          };
+          output.push_str(&replacement);
+          emitted += replacement.chars().count();
+          shifts.push((emitted + 1, (i + 1 + lead) as isize - emitted as isize));
+        }
+        None => {
+          output.push(c);
+          emitted += 1;
+        }
+      }
+      i += 1;
+    }
+    columns.shifts.push(shifts);
+  }
+
+  (output, findings, columns)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_strips_bidi_and_invisible_characters() {
+    let text =
+      "let access = \"user\u{202E} \u{2066}// admin\u{2069} \u{2066}\";\nlet a\u{200B}b = 1;";
+    let (clean, findings) = sanitize_unicode(text, "auth.rs", UnicodePolicy::Strip);
+    assert_eq!(clean, "let access = \"user // admin \";\nlet ab = 1;");
+
+    let rules: Vec<(&str, usize)> = findings
+      .iter()
+      .map(|f| (f.rule_id.as_str(), f.line))
+      .collect();
+    assert_eq!(
+      rules,
+      vec![
+        ("unicode-bidi", 1),
+        ("unicode-bidi", 1),
+        ("unicode-bidi", 1),
+        ("unicode-bidi", 1),
+        ("unicode-invisible", 2),
+      ]
+    );
+    assert_eq!(findings[0].column, 19);
+  }
+
+  #[test]
+  fn test_column_map_points_into_the_input() {
+    let text = "\u{FEFF}a\u{200B}b = 1\nx\u{200B}\u{200B}y";
+    let (clean, _, columns) =
+      sanitize_unicode_mapped(text, "", UnicodePolicy::Strip, &FingerprintKey::default());
+    assert_eq!(clean, "ab = 1\nxy");
+    assert_eq!(columns.original_column(1, 1), 2);
+    assert_eq!(columns.original_column(1, 2), 4);
+    assert_eq!(columns.original_column(2, 2), 4);
+
+    let (clean, _, columns) = sanitize_unicode_mapped(
+      "x\u{200B}y",
+      "",
+      UnicodePolicy::Escape,
+      &FingerprintKey::default(),
+    );
+    assert_eq!(clean, "x<U+200B>y");
+    assert_eq!(columns.original_column(1, 10), 3);
+  }
+
+  #[test]
+  fn test_escape_and_report_only_policies() {
+    let text = "x\u{200B}y";
+    assert_eq!(
+      sanitize_unicode(text, "", UnicodePolicy::Escape).0,
+      "x<U+200B>y"
+    );
+    assert_eq!(
+      sanitize_unicode(text, "", UnicodePolicy::ReportOnly).0,
+      text
+    );
+  }
+
+  #[test]
+  fn test_homoglyph_identifiers_are_normalized() {
+    // "раypal" starts with Cyrillic р and а
+    let text = "if is_\u{0440}\u{0430}ypal_user() { привет(); }";
+    let (clean, findings) = sanitize_unicode(text, "", UnicodePolicy::Strip);
+    assert_eq!(clean, "if is_paypal_user() { привет(); }");
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].rule_id, "unicode-homoglyph");
+    assert_eq!(findings[0].column, 4);
+  }
+
+  #[test]
+  fn test_leading_bom_and_emoji_joiners_are_kept_quiet() {
+    let text = "\u{FEFF}family = \"👨\u{200D}👩\u{200D}👧\"";
+    let (clean, findings) = sanitize_unicode(text, "", UnicodePolicy::Strip);
+    assert_eq!(clean, "family = \"👨\u{200D}👩\u{200D}👧\"");
+    assert!(findings.is_empty());
+  }
+}
@@ -1,6 +1,9 @@
 //! Integration tests for the CLI commands
 
+mod common;
+
 use assert_cmd::cargo::cargo_bin_cmd;
+use common::temp_repo;
 use predicates::prelude::*;
 
 #[test]
@@ -32,3 +35,143 @@ fn test_version_short_flag() {
         .success()
         .stdout(predicate::str::starts_with("toak "));
 }
+
+#[test]
+fn test_scan_fails_on_secret() {
+    let dir = temp_repo("scan-fail", &[("config.js", "const API_KEY = \"live-123\";\n")]);
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["scan", "--format", "json", "-d"]).arg(&dir);
+
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("\"rule_id\": \"assigned-secret\""))
+        .stdout(predicate::str::contains("live-123").not());
+    assert!(!dir.join(".toak").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_scan_passes_below_threshold_and_with_suppressions() {
+    let dir = temp_repo(
+        "scan-pass",
+        &[
+            ("hashes.txt", "da39a3ee5e6b4b0d3255bfef95601890afd80709\n"),
+            ("config.js", "const API_KEY = \"fixture\"; // toak:allow\n"),
+        ],
+    );
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["scan", "--severity-threshold", "medium", "-d"]).arg(&dir);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1 findings, 1 suppressed"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_scan_update_baseline_accepts_existing_findings() {
+    let dir = temp_repo("scan-baseline", &[("config.js", "const API_KEY = \"legacy-123\";\n")]);
+
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["scan", "--update-baseline", "-d"]).arg(&dir);
+    cmd.assert().success();
+
+    let baseline = std::fs::read_to_string(dir.join(".toak/secrets-baseline.json")).unwrap();
+    assert!(!baseline.contains("legacy-123"));
+    assert!(dir.join(".toak/fingerprint-key").is_file());
+
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["scan", "-d"]).arg(&dir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("0 findings, 1 suppressed"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_model_list_marks_default() {
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["model", "list"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("onnx-community/embeddinggemma-300m-ONNX (default)"))
+        .stdout(predicate::str::contains("Xenova/bge-small-en-v1.5"));
+}
+
+#[test]
+fn test_model_dir_requires_manifest() {
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["search", "query", "--model-dir", "no-such-model-dir"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("no-such-model-dir is not a model directory"));
+}
+
+#[test]
+fn test_ignored_lockfiles_are_not_summarized() {
+    let dir = temp_repo("lockfiles", &[
+        ("main.go", "package main\n"),
+        ("go.sum", "github.com/acme/kept v1.2.0 h1:abc=\n"),
+    ]);
+    std::fs::create_dir_all(dir.join("old")).unwrap();
+    std::fs::write(dir.join("old/go.sum"), "github.com/acme/ignored v0.1.0 h1:def=\n").unwrap();
+    std::fs::write(dir.join("old/.aiignore"), "go.sum\n").unwrap();
+    let status = std::process::Command::new("git").args(["add", "-A"]).current_dir(&dir).status().unwrap();
+    assert!(status.success());
+
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["generate", "--quiet", "--model", "toak/hashing-64", "-d"])
+        .arg(&dir)
+        .arg("-o")
+        .arg(dir.join("prompt.md"));
+    cmd.assert().success();
+
+    let prompt = std::fs::read_to_string(dir.join("prompt.md")).unwrap();
+    assert!(prompt.contains("github.com/acme/kept v1.2.0"), "{}", prompt);
+    assert!(!prompt.contains("github.com/acme/ignored"), "{}", prompt);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_generate_counts_secrets_and_personal_data_apart() {
+    let dir = temp_repo("pii-count", &[(
+        "config.js",
+        "const API_KEY = \"live-123\";\nconst OWNER = \"jane.doe@corp.example\";\n",
+    )]);
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["generate", "--redact-pii", "--model", "toak/hashing-64", "-d"])
+        .arg(&dir)
+        .arg("-o")
+        .arg(dir.join("prompt.md"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Redacted 1 secrets"))
+        .stdout(predicate::str::contains("Redacted 1 personal data values"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_generate_and_search_with_hashing_model() {
+    let dir = temp_repo("hashing", &[
+        ("parser.rs", "pub fn parse_config_file(path: &str) -> Config { todo!() }\n"),
+        ("screen.rs", "pub fn draw_widgets_on_screen(screen: &mut Screen) { todo!() }\n"),
+    ]);
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["generate", "--quiet", "--model", "toak/hashing-128", "-d"])
+        .arg(&dir)
+        .arg("-o")
+        .arg(dir.join("prompt.md"));
+    cmd.assert().success();
+
+    // Searches pick the hashing model up from the database
+    let mut cmd = cargo_bin_cmd!("toak");
+    cmd.args(["search", "parse the config file", "-n", "1", "-f"]).arg(dir.join("embeddings.json"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("model: toak/hashing-128"))
+        .stdout(predicate::str::contains("parser.rs"))
+        .stdout(predicate::str::contains("screen.rs").not());
+    let _ = std::fs::remove_dir_all(&dir);
+}
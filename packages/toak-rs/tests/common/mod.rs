@@ -0,0 +1,22 @@
+//! Helpers shared by the integration tests
+
+/// Creates a throwaway git repository containing the given files.
+pub fn temp_repo(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+  let dir = std::env::temp_dir().join(format!("toak-test-{}-{}", name, std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+  std::fs::create_dir_all(&dir).unwrap();
+  for (path, content) in files {
+    std::fs::write(dir.join(path), content).unwrap();
+  }
+  let git = |args: &[&str]| {
+    let status = std::process::Command::new("git")
+      .args(args)
+      .current_dir(&dir)
+      .status()
+      .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+  };
+  git(&["init", "-q"]);
+  git(&["add", "."]);
+  dir
+}
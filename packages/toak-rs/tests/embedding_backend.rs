@@ -0,0 +1,164 @@
+//! Tests of the OpenAI-compatible embedding backend against a local mock server
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use toak_rs::{EmbeddingBackend, OpenAiBackend, OpenAiConfig};
+
+/// A canned HTTP response: status, extra headers and body
+type Reply = (u16, Vec<(&'static str, String)>, String);
+
+/// Serves one connection per request on a local port. `respond` gets the parsed request body
+/// and the number of the request; the bodies received are returned through the shared log.
+fn mock_server<F>(respond: F) -> (String, Arc<Mutex<Vec<serde_json::Value>>>)
+where
+  F: Fn(&serde_json::Value, usize) -> Reply + Send + 'static,
+{
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+  let requests = Arc::new(Mutex::new(Vec::new()));
+  let log = requests.clone();
+
+  std::thread::spawn(move || {
+    for stream in listener.incoming() {
+      let mut stream = stream.unwrap();
+      let mut reader = BufReader::new(stream.try_clone().unwrap());
+      let mut content_length = 0;
+      let mut request_line = String::new();
+      reader.read_line(&mut request_line).unwrap();
+      assert!(
+        request_line.starts_with("POST /v1/embeddings "),
+        "unexpected request {}",
+        request_line
+      );
+      loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+          break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+          if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.trim().parse().unwrap();
+          }
+        }
+      }
+      let mut body = vec![0; content_length];
+      reader.read_exact(&mut body).unwrap();
+      let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+      let number = {
+        let mut log = log.lock().unwrap();
+        log.push(body.clone());
+        log.len()
+      };
+      let (status, headers, reply) = respond(&body, number);
+      let mut response = format!("HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n", status, reply.len());
+      for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+      }
+      response.push_str("\r\n");
+      response.push_str(&reply);
+      stream.write_all(response.as_bytes()).unwrap();
+    }
+  });
+
+  (base_url, requests)
+}
+
+/// Embeds each input as [length, index within the request], listed in reverse order to check
+/// that the backend orders them by index.
+fn embeddings_reply(body: &serde_json::Value) -> Reply {
+  let inputs = body["input"].as_array().unwrap();
+  let data: Vec<_> = inputs
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, input)| serde_json::json!({"object": "embedding", "index": index, "embedding": [input.as_str().unwrap().len() as f32, index as f32]}))
+        .collect();
+  (
+    200,
+    Vec::new(),
+    serde_json::json!({"object": "list", "data": data}).to_string(),
+  )
+}
+
+fn config(base_url: &str) -> OpenAiConfig {
+  OpenAiConfig {
+    retry_backoff: Duration::from_millis(10),
+    ..OpenAiConfig::new(base_url, "test-embedder")
+  }
+}
+
+#[test]
+fn test_openai_backend_batches_in_order() {
+  let (base_url, requests) = mock_server(|body, _| embeddings_reply(body));
+  let mut backend = OpenAiBackend::new(OpenAiConfig {
+    batch_size: 2,
+    ..config(&base_url)
+  })
+  .unwrap();
+
+  let embeddings = backend
+    .embed(&["a", "bb", "ccc", "dddd", "eeeee"], None)
+    .unwrap();
+
+  let lengths: Vec<f32> = embeddings.iter().map(|embedding| embedding[0]).collect();
+  assert_eq!(lengths, [1.0, 2.0, 3.0, 4.0, 5.0]);
+  let requests = requests.lock().unwrap();
+  assert_eq!(requests.len(), 3);
+  assert_eq!(requests[0]["model"], "test-embedder");
+  assert_eq!(requests[2]["input"], serde_json::json!(["eeeee"]));
+}
+
+#[test]
+fn test_openai_backend_retries_server_errors() {
+  let (base_url, requests) = mock_server(|body, number| match number {
+    1 => (
+      503,
+      Vec::new(),
+      "{\"error\": \"loading model\"}".to_string(),
+    ),
+    2 => (
+      429,
+      vec![("Retry-After", "0".to_string())],
+      "{\"error\": \"slow down\"}".to_string(),
+    ),
+    _ => embeddings_reply(body),
+  });
+  let mut backend = OpenAiBackend::new(config(&base_url)).unwrap();
+
+  let embedding = backend.embed_one("query").unwrap();
+
+  assert_eq!(embedding, [5.0, 0.0]);
+  assert_eq!(requests.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn test_openai_backend_fails_on_client_errors() {
+  let (base_url, requests) = mock_server(|_, _| {
+    (
+      400,
+      Vec::new(),
+      "{\"error\": \"input too long\"}".to_string(),
+    )
+  });
+  let mut backend = OpenAiBackend::new(config(&base_url)).unwrap();
+
+  let error = backend.embed(&["text"], None).unwrap_err().to_string();
+
+  assert!(
+    error.contains("400") && error.contains("input too long"),
+    "{}",
+    error
+  );
+  assert_eq!(requests.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_openai_backend_rejects_invalid_urls() {
+  assert!(OpenAiBackend::new(config("localhost:8080/v1")).is_err());
+  assert!(OpenAiBackend::new(config("http:///v1")).is_err());
+  assert!(OpenAiBackend::new(config("https://api.example.com/v1")).is_ok());
+}
@@ -1,3 +1,6 @@
+mod common;
+
+use common::temp_repo;
 use toak_rs::prelude::*;
 
 #[test]
@@ -90,4 +93,106 @@ fn test_chunk_count() {
 
     assert_eq!(count, metadata.total_chunks, "Chunk count should match metadata");
     println!("Database contains {} chunks", count);
-}
\ No newline at end of file
+}
+
+/// Generates a database of a one-file repository embedded by the hashing embedder with
+/// `dimension` dimensions, then applies `edit` to its JSON. Returns the repository and database.
+async fn hashing_database(name: &str, dimension: usize, edit: impl FnOnce(&mut serde_json::Value)) -> (std::path::PathBuf, std::path::PathBuf) {
+    let dir = temp_repo(name, &[("lib.rs", "pub fn load_config(path: &str) -> Config {\n    parse_config_file(path)\n}\n")]);
+    let output = dir.join("embeddings.json");
+    let options = JsonDatabaseOptions {
+        dir: dir.clone(),
+        output_file_path: output.clone(),
+        embedding_backend: EmbeddingBackendConfig::Hashing(dimension),
+        ..Default::default()
+    };
+    JsonDatabaseGenerator::new(options).unwrap().generate_database().await.unwrap();
+    let mut database: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    edit(&mut database);
+    std::fs::write(&output, database.to_string()).unwrap();
+    (dir, output)
+}
+
+#[tokio::test]
+async fn test_search_refuses_other_models() {
+    let (dir, output) = hashing_database("mismatch", 64, |_| {}).await;
+    let backend = EmbeddingBackendConfig::Hashing(128).create().unwrap();
+
+    let error = SemanticSearch::with_backend(&output, backend).err().expect("model mismatch accepted").to_string();
+
+    assert!(error.contains("toak/hashing-64") && error.contains("toak/hashing-128"), "{}", error);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_search_checks_dimensions_and_normalization() {
+    let (dir, output) = hashing_database("dimension", 64, |database| database["dimension"] = 32.into()).await;
+    let mut search = SemanticSearch::new(&output).unwrap();
+
+    let error = search.search("query", 1).unwrap_err().to_string();
+
+    assert!(error.contains("64 dimensions") && error.contains("have 32"), "{}", error);
+    assert_eq!(search.metadata().dimension, Some(32));
+
+    // A query normalized unlike the database is searched, and the mismatch reported
+    let (dir_b, output) = hashing_database("normalized", 64, |database| database["normalized"] = false.into()).await;
+    let mut search = SemanticSearch::new(&output).unwrap();
+    assert!(!search.metadata().normalization_mismatch);
+    assert_eq!(search.search("load the config", 1).unwrap().len(), 1);
+    assert!(search.metadata().normalization_mismatch);
+
+    std::fs::remove_dir_all(dir).unwrap();
+    std::fs::remove_dir_all(dir_b).unwrap();
+}
+
+#[tokio::test]
+async fn test_generate_and_search_with_hashing_embedder() {
+    let dir = temp_repo("hashing", &[
+        ("config.rs", "/// Reads the settings file from disk\npub fn load_config(path: &str) -> Config {\n    parse_config_file(path)\n}\n"),
+        ("render.rs", "/// Draws the widget tree onto the screen\npub fn render_widgets(screen: &mut Screen) {\n    screen.draw_all();\n}\n"),
+        ("network.rs", "/// Retries failed HTTP requests with backoff\npub fn retry_request(request: Request) -> Response {\n    send_with_backoff(request)\n}\n"),
+    ]);
+    let output = dir.join("embeddings.json");
+    let options = JsonDatabaseOptions {
+        dir: dir.clone(),
+        output_file_path: output.clone(),
+        verbose: false,
+        embedding_backend: EmbeddingBackendConfig::Hashing(128),
+        ..Default::default()
+    };
+
+    let result = JsonDatabaseGenerator::new(options).unwrap().generate_database().await.unwrap();
+    assert!(result.success);
+    assert_eq!(result.total_chunks, 3);
+    assert_eq!(result.total_locations, 3);
+
+    // The database records the hashing model, so it is searched with the same embedder
+    let mut search = SemanticSearch::new(&output).unwrap();
+    assert_eq!(search.metadata().model, "toak/hashing-128");
+    assert_eq!(search.metadata().dimension, Some(128));
+    assert_eq!(search.metadata().normalized, Some(true));
+
+    let results = search.search("load the config file", 3).unwrap();
+    assert_eq!(results[0].file_path, "config.rs");
+    let results = search.search("retry the http request", 3).unwrap();
+    assert_eq!(results[0].file_path, "network.rs");
+    assert!(results[0].similarity > results[1].similarity);
+
+    // Embeddings are the same on every run
+    let first = std::fs::read_to_string(&output).unwrap();
+    let options = JsonDatabaseOptions {
+        dir: dir.clone(),
+        output_file_path: output.clone(),
+        verbose: false,
+        embedding_backend: EmbeddingBackendConfig::Hashing(128),
+        ..Default::default()
+    };
+    JsonDatabaseGenerator::new(options).unwrap().generate_database().await.unwrap();
+    let embeddings = |json: &str| -> Vec<Vec<f32>> {
+        let database: EmbeddingsDatabase = serde_json::from_str(json).unwrap();
+        database.chunks.into_iter().map(|chunk| chunk.embedding).collect()
+    };
+    assert_eq!(embeddings(&first), embeddings(&std::fs::read_to_string(&output).unwrap()));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}